@@ -3,6 +3,31 @@ use rand::{thread_rng, Rng};
 use rand::rngs::ThreadRng;
 use num_traits::{zero, one};
 use num_traits::ToPrimitive;
+use std::fmt;
+
+/// An enum that represents why a modular multiplicative inverse couldn't be calculated
+/// 
+/// # Variants
+/// * `NoInverse` - The number and the modulo aren't coprime, so there is no inverse
+/// * `StepLimitReached` - The algorithm took more steps than it can for inputs of that size
+/// 
+#[derive(Debug, PartialEq)]
+pub enum InverseError {
+    NoInverse,
+    StepLimitReached
+}
+
+/// implement display for inverse errors for easy printing
+impl fmt::Display for InverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InverseError::NoInverse => write!(f, "there is no modular multiplicative inverse"),
+            InverseError::StepLimitReached => write!(f, "the modular multiplicative inverse took more steps than it can")
+        }
+    }
+}
+
+impl std::error::Error for InverseError {}
 
 /// Helper functions for BigInt operations
 /// 
@@ -23,9 +48,14 @@ pub fn bigint(num: &str) -> BigInt {
 /// * `m` - A reference to a BigInt being the modulo
 /// 
 /// # Returns
-/// A BigInt representation of the modulo
+/// A BigInt representation of the modulo, or 0 if m is 0 since there is no such modulo
 /// 
 pub fn modulo(x: &BigInt, m: &BigInt) -> BigInt {
+    // would divide by zero
+    if m == &zero() {
+        return zero();
+    }
+
     ((x % m) + m) % m
 }
 
@@ -85,6 +115,9 @@ pub fn entropy() -> BigInt {
 
 /// Helper function to calculate the modular multiplicative inverse of a number.
 /// This function uses the extended euclidean algorithm to calculate the modular multiplicative inverse.
+/// Each step of the algorithm is an iteration of a loop rather than a recursive call, so large inputs can't overflow the stack.
+/// The remainders shrink by at least half every two steps, so more than 2 steps per bit of the inputs would mean
+/// the loop is never going to end, and it stops with an error instead.
 /// 
 /// # Arguments
 /// * `n` - A reference to a BigInt that is the number to be modded
@@ -93,37 +126,160 @@ pub fn entropy() -> BigInt {
 /// * `t2` - An optional BigInt that is the second value in the calculation (default is 1)
 /// 
 /// # Returns
-/// A BigInt representation of the modular multiplicative inverse
+/// A Result<BigInt, InverseError> which is the modular multiplicative inverse, or an error if there is none or the step limit was reached
 /// 
 pub fn modular_multiplicative_inverse(
+    n: &BigInt,
+    b: BigInt,
+    t1: Option<BigInt>,
+    t2: Option<BigInt>,
+) -> Result<BigInt, InverseError> {
+    // one more step in case b starts bigger than n, the first step only swaps them
+    let max_steps: u64 = 2 * n.bits().max(b.bits()) + 2;
+
+    inverse_within_steps(n, b, t1, t2, max_steps)
+}
+
+/// the extended euclidean algorithm of modular_multiplicative_inverse, stopping after max_steps steps
+/// 
+/// # Arguments
+/// * `n` - A reference to a BigInt that is the number to be modded
+/// * `b` - A BigInt that is the modulo
+/// * `t1` - An optional BigInt that is the first value in the calculation (default is 0)
+/// * `t2` - An optional BigInt that is the second value in the calculation (default is 1)
+/// * `max_steps` - A u64 that is the most steps the algorithm can take
+/// 
+/// # Returns
+/// A Result<BigInt, InverseError> which is the modular multiplicative inverse, or an error if there is none or it took more than max_steps
+/// 
+fn inverse_within_steps(
     n: &BigInt,
     mut b: BigInt,
     t1: Option<BigInt>,
     t2: Option<BigInt>,
-) -> BigInt {
-    let t1: BigInt = t1.unwrap_or(zero()); // set default value for t1
-    let t2: BigInt = t2.unwrap_or(one());// set default value for t2
+    max_steps: u64
+) -> Result<BigInt, InverseError> {
+    let mut n: BigInt = n.clone();
+    let mut t1: BigInt = t1.unwrap_or(zero()); // set default value for t1
+    let mut t2: BigInt = t2.unwrap_or(one());// set default value for t2
 
-    if n == &zero() || b == zero() {
-        return zero();
+    // only the first b can be negative, every next one is a remainder
+    if b < zero() {
+        b = modulo(&b, &n);
     }
 
-    if b < zero() {
-        b = modulo(&b, n);
+    for _ in 0..max_steps {
+        if n == zero() || b == zero() {
+            return Err(InverseError::NoInverse);
+        }
+
+        let q: BigInt = &n / &b;
+        let r: BigInt = modulo(&n, &b);
+
+        let t3: BigInt = &t1 - &q * &t2;
+
+        if r == zero() && b != one() {
+            return Err(InverseError::NoInverse);
+        }
+
+        if r == zero() {
+            return Ok(t2);
+        }
+
+        // next step of the algorithm is on (b, r) with (t2, t3)
+        n = b;
+        b = r;
+        t1 = t2;
+        t2 = t3;
     }
 
-    let q: BigInt = n / &b;
-    let r: BigInt = modulo(n, &b);
+    Err(InverseError::StepLimitReached)
+}
 
-    let t3: BigInt = t1 - &q * &t2;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if r == zero() && b != one() {
-        return zero();
+    #[test]
+    fn zero_modulus_gives_zero() {
+        assert_eq!(modulo(&BigInt::from(17), &zero()), zero());
+        assert_eq!(modulo(&BigInt::from(-17), &zero()), zero());
+    }
+
+    #[test]
+    fn negative_values_wrap_into_the_modulus() {
+        assert_eq!(modulo(&BigInt::from(17), &BigInt::from(5)), BigInt::from(2));
+        assert_eq!(modulo(&BigInt::from(-17), &BigInt::from(5)), BigInt::from(3));
+    }
+
+    #[test]
+    fn small_inverse_matches_the_known_one() {
+        // (5 * x) mod 7 = 1 => 3
+        let inverse: BigInt = modular_multiplicative_inverse(&BigInt::from(7), BigInt::from(5), None, None).unwrap();
+        assert_eq!(modulo(&inverse, &BigInt::from(7)), BigInt::from(3));
+
+        // b bigger than n only takes one more step
+        let inverse: BigInt = modular_multiplicative_inverse(&BigInt::from(7), BigInt::from(12), None, None).unwrap();
+        assert_eq!(modulo(&inverse, &BigInt::from(7)), BigInt::from(3));
+    }
+
+    #[test]
+    fn large_inverses_near_the_field_prime_match_fermat() {
+        let p: BigInt = crate::secp256k1::Secp256k1::new().p;
+
+        let values: [BigInt; 5] = [&p - 1, &p - 2, &p / 3, BigInt::from(2).pow(255) + 19, BigInt::from(5) - &p];
+        for x in values {
+            let inverse: BigInt = modulo(&modular_multiplicative_inverse(&p, x.clone(), None, None).unwrap(), &p);
+
+            // p is prime so x^(p-2) is the inverse of x
+            assert_eq!(inverse, modulo(&x, &p).modpow(&(&p - 2), &p));
+            assert_eq!(modulo(&(&x * &inverse), &p), one());
+        }
+    }
+
+    #[test]
+    fn no_inverse_is_an_error() {
+        // the two aren't coprime
+        for k in [0, 4, 6, 9] {
+            assert_eq!(modular_multiplicative_inverse(&BigInt::from(12), BigInt::from(k), None, None), Err(InverseError::NoInverse));
+        }
+
+        assert_eq!(modular_multiplicative_inverse(&BigInt::from(8), BigInt::from(6), None, None), Err(InverseError::NoInverse));
+        assert_eq!(modular_multiplicative_inverse(&BigInt::from(7), BigInt::from(-7), None, None), Err(InverseError::NoInverse));
+    }
+
+    /// the consecutive fibonacci numbers after 1 and 2, the worst case for the euclidean algorithm
+    fn consecutive_fibonacci(steps: usize) -> (BigInt, BigInt) {
+        let (mut a, mut b): (BigInt, BigInt) = (BigInt::from(1), BigInt::from(2));
+        for _ in 0..steps {
+            let c: BigInt = &a + &b;
+            a = b;
+            b = c;
+        }
+
+        (a, b)
+    }
+
+    #[test]
+    fn inverse_of_consecutive_fibonacci_numbers_fits_in_the_step_limit() {
+        let (a, b) = consecutive_fibonacci(5000);
+
+        let inverse: BigInt = modular_multiplicative_inverse(&b, a.clone(), None, None).unwrap();
+        assert_eq!(modulo(&(&a * inverse), &b), one::<BigInt>());
+
+        let inverse: BigInt = modular_multiplicative_inverse(&a, b.clone(), None, None).unwrap();
+        assert_eq!(modulo(&(&b * inverse), &a), one::<BigInt>());
     }
 
-    if r == zero() {
-        t2
-    } else {
-        modular_multiplicative_inverse(&b, r, Some(t2), Some(t3))
+    #[test]
+    fn running_out_of_steps_is_an_error() {
+        // fibonacci numbers take a step for each of them
+        let (a, b) = consecutive_fibonacci(50);
+
+        assert_eq!(inverse_within_steps(&b, a.clone(), None, None, 10), Err(InverseError::StepLimitReached));
+        assert_eq!(inverse_within_steps(&b, a.clone(), None, None, 0), Err(InverseError::StepLimitReached));
+
+        let inverse: BigInt = inverse_within_steps(&b, a.clone(), None, None, 60).unwrap();
+        assert_eq!(modulo(&(&a * inverse), &b), one::<BigInt>());
     }
 }
\ No newline at end of file
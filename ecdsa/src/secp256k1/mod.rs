@@ -15,7 +15,8 @@ mod precomputed_points {
 
     pub static PRECOMPUTED_POINTS: once_cell::sync::Lazy<Vec<Point>> =
         once_cell::sync::Lazy::new(|| {
-            precompute_points(Secp256k1::new().g, W)
+            // the multiples of the generator are fixed, none of them is the identity so they all have an inverse
+            precompute_points(Secp256k1::new().g, W).expect("the generator has precomputed points")
         });
 }
 
@@ -30,4 +31,5 @@ mod signature;
 
 pub use curve::Secp256k1;
 pub use point::Point;
-pub use signature::{Signature, sign, verify_signature};
+pub use signature::{Signature, sign, verify_signature, verify_batch};
+pub use crate::math::InverseError;
@@ -3,7 +3,7 @@ use std::hash::Hash;
 use num_bigint::BigInt;
 use num_traits::zero;
 use serde::ser::{Serialize, Serializer, SerializeStruct};
-use crate::{math::{modular_multiplicative_inverse, modulo, bigint, calculate_wnaf}, secp256k1::FP};
+use crate::{math::{modular_multiplicative_inverse, modulo, bigint, calculate_wnaf, InverseError}, secp256k1::FP};
 use serde::de::{Deserialize, Deserializer};
 
 #[derive(Eq, PartialEq, Hash, Clone)]
@@ -77,9 +77,9 @@ impl Point {
     /// * `pre_comp` - A reference to a Vec<Point> that is the precomputed points
     /// 
     /// # Returns
-    /// A Point that is the result of the multiplication
+    /// A Result<Point, InverseError> which is the result of the multiplication, or an error if a step had no inverse to divide by
     /// 
-    pub fn multiply(self, n: BigInt, width: u32, pre_comp: &std::vec::Vec<Point>) -> Point {
+    pub fn multiply(self, n: BigInt, width: u32, pre_comp: &std::vec::Vec<Point>) -> Result<Point, InverseError> {
         let wnaf: Vec<i8> = calculate_wnaf(width, n);
 
         let mut q: Point = Point::identity();
@@ -87,14 +87,14 @@ impl Point {
         let mut i: i32 = (wnaf.len() as i32) - 1;
 
         while i > -1 {
-            q = q.double();
+            q = q.double()?;
 
             let n: usize = i as usize;
 
             if wnaf[n] > 0 {
                 let d: i8 = (wnaf[n] - 1) / 2;
 
-                q = q.add(&pre_comp[d as usize]);
+                q = q.add(&pre_comp[d as usize])?;
             } else if wnaf[n] < 0 {
                 let d: i8 = (-wnaf[n] - 1) / 2;
 
@@ -103,13 +103,13 @@ impl Point {
                     y: pre_comp[d as usize].y.clone() * -1
                 };
 
-                q = q.add(&z);
+                q = q.add(&z)?;
             }
 
             i = i - 1;
         }
 
-        q
+        Ok(q)
     } 
 
     /// returns the identity point (0, 0)
@@ -120,28 +120,56 @@ impl Point {
         }
     }
 
+    /// checks if the point is on the secp256k1 curve, which is y^2 = x^3 + 7 (mod P) 
+    /// with both coordinates in the prime field. the identity point is not on the curve
+    /// 
+    /// # Returns
+    /// A bool which is true if the point is on the curve, false otherwise
+    /// 
+    pub fn is_on_curve(&self) -> bool {
+        let fp: &BigInt = &bigint(FP);
+        let field: std::ops::Range<BigInt> = zero()..fp.clone();
+
+        if !field.contains(&self.x) || !field.contains(&self.y) {
+            return false;
+        }
+
+        modulo(&(&self.y * &self.y - &self.x * &self.x * &self.x - 7), fp) == zero()
+    }
+
+    /// returns the address of the point, the hash of its display form.
+    /// shorter than the point itself, which makes it easier to index and show
+    pub fn to_address(&self) -> String {
+        sha256::hash(self.to_string())
+    }
+
     /// doubles a point ie, it adds the point to itself (mod fp) using these formulas
     /// L = [ (3*X^2) / 2*Y ] mod P
     /// Xr = [ L^2 - 2*X ] mod P
     /// Yr = [ L*(X - Xr) - Y ] mod P
     /// 
     /// # Returns
-    /// A Point that is the result of the doubling
+    /// A Result<Point, InverseError> which is the result of the doubling, or an error if the inverse couldn't be calculated
     /// 
-    fn double(&self) -> Point {
+    fn double(&self) -> Result<Point, InverseError> {
         let fp: &BigInt = &bigint(FP);
 
         // we use the modular multiplicative inverse to not have to divide
-        let lambda: BigInt = modulo(&(3 * &self.x * &self.x 
-            * modular_multiplicative_inverse(fp, 2 * &self.y, None, None)), 
-            fp);
+        let inverse: BigInt = match modular_multiplicative_inverse(fp, 2 * &self.y, None, None) {
+            Ok(inverse) => inverse,
+            // y is 0 mod P, the tangent is vertical so the double is the identity
+            Err(InverseError::NoInverse) => return Ok(Point::identity()),
+            Err(error) => return Err(error)
+        };
+
+        let lambda: BigInt = modulo(&(3 * &self.x * &self.x * inverse), fp);
         let rx: BigInt = modulo(&(&lambda * &lambda - &self.x - &self.x), fp);
         let ry: BigInt = modulo(&(lambda * (&self.x - &rx) - &self.y), fp);
 
-        Point {
+        Ok(Point {
             x: rx,
             y: ry
-        }
+        })
     }
 
     /// add implementation adds a point to another using following formulas
@@ -153,30 +181,38 @@ impl Point {
     /// * `other` - A reference to a Point that is the point to add
     /// 
     /// # Returns
-    /// A Point that is the result of the addition
+    /// A Result<Point, InverseError> which is the result of the addition, or an error if the inverse couldn't be calculated
     /// 
-    pub fn add(self, other: &Point) -> Point {
+    pub fn add(self, other: &Point) -> Result<Point, InverseError> {
         if self.x == other.x && self.y == (&other.y * -1) { // check P2 = -P1, vertical line, thus P1 + P2 = 0
-            Point::identity()
+            Ok(Point::identity())
         } else if self.x == other.x && self.y == other.y { // P1 == P2, use point doubling
             self.double()
         } else if self.x == zero() && self.y == zero() { // 0 + P2 = P2
-            other.clone()
+            Ok(other.clone())
         } else if other.x == zero() && other.y == zero() { // P1 + 0 = P1
-            self
+            Ok(self)
         } else {
             let fp: &BigInt = &bigint(FP);
-            let lambda: BigInt = modulo(
-                &((&other.y - &self.y) 
-                * modular_multiplicative_inverse(fp, &other.x - &self.x, None, None)
-            ), fp);
+            let inverse: BigInt = match modular_multiplicative_inverse(fp, &other.x - &self.x, None, None) {
+                Ok(inverse) => inverse,
+                // same x mod P, the points are either the same or opposite
+                Err(InverseError::NoInverse) => return if modulo(&self.y, fp) == modulo(&other.y, fp) {
+                    self.double()
+                } else {
+                    Ok(Point::identity())
+                },
+                Err(error) => return Err(error)
+            };
+
+            let lambda: BigInt = modulo(&((&other.y - &self.y) * inverse), fp);
             let rx: BigInt = modulo(&(&lambda * &lambda - &other.x - &self.x), fp);
             let ry: BigInt = modulo(&(lambda * (&self.x - &rx) - &self.y), fp);
 
-            Point {
+            Ok(Point {
                 x: rx,
                 y: ry
-            }
+            })
         }
     }
 }
@@ -188,18 +224,18 @@ impl Point {
 /// * `w` - A u32 that is the width of the window
 /// 
 /// # Returns
-/// A Vec<Point> that is the precomputed points
+/// A Result<Vec<Point>, InverseError> which is the precomputed points, or an error if a step had no inverse to divide by
 /// 
-pub fn precompute_points(mut q: Point, w: u32) -> Vec<Point> {
+pub fn precompute_points(mut q: Point, w: u32) -> Result<Vec<Point>, InverseError> {
     let mut p: Vec<Point> = vec![q.clone()];
 
-    q = q.double();
+    q = q.double()?;
 
     for j in 1..(1 << (w - 1)) {
         let mut buffer: Point = q.clone();
-        buffer = buffer.add(&p[(j - 1) as usize]);
+        buffer = buffer.add(&p[(j - 1) as usize])?;
         p.push(buffer);
     }
 
-    p
+    Ok(p)
 }
\ No newline at end of file
@@ -1,14 +1,15 @@
 //! for more info on the maths here: https://cryptobook.nakov.com/digital-signatures/ecdsa-sign-verify-messages
 
 use core::fmt;
-use num_bigint::BigInt;
-use num_traits::zero;
+use std::ops::Range;
+use num_bigint::{BigInt, Sign};
+use num_traits::{one, zero};
 use sha256::hash;
 use super::{Secp256k1, Point, W};
-use crate::{math::{self, bigint, entropy, modular_multiplicative_inverse, modulo}, 
+use crate::{math::{bigint, entropy, modular_multiplicative_inverse, modulo, InverseError}, 
             secp256k1::get_curve_precomputed_points};
 use serde::ser::{Serialize, Serializer, SerializeStruct};
-use serde::de::{Deserialize, Deserializer};
+use serde::de::{self, Deserialize, Deserializer};
 
 /// Signature struct that holds the r and sigma values of a digital signature
 #[derive(Clone)]
@@ -27,6 +28,84 @@ impl Signature {
     pub fn get_empty() -> Self {
         Signature { r: zero(), s: zero() }
     }
+
+    /// checks that r and s are both in [1, n - 1], the only values a signature can have.
+    /// the empty signature is also accepted since it's what miner rewards carry
+    /// 
+    /// # Returns
+    /// a boolean that is true if the signature's values are in range and false otherwise
+    /// 
+    pub fn is_in_range(&self) -> bool {
+        if self.r == zero() && self.s == zero() {
+            return true;
+        }
+
+        let n: BigInt = Secp256k1::new().n;
+        let range: Range<BigInt> = one()..n;
+
+        range.contains(&self.r) && range.contains(&self.s)
+    }
+
+    /// returns the signature as 64 bytes, r then s, each big-endian and left-padded to 32 bytes
+    ///
+    /// # Returns
+    /// a [u8; 64] that holds the fixed width encoding of the signature
+    ///
+    pub fn to_fixed(&self) -> [u8; 64] {
+        let mut fixed: [u8; 64] = [0; 64];
+
+        // r and s are smaller than n so they always fit in 32 bytes
+        let (_, r_bytes) = self.r.to_bytes_be();
+        let (_, s_bytes) = self.s.to_bytes_be();
+        fixed[32 - r_bytes.len()..32].copy_from_slice(&r_bytes);
+        fixed[64 - s_bytes.len()..].copy_from_slice(&s_bytes);
+
+        fixed
+    }
+
+    /// reads a signature from its fixed width encoding, r then s as 32 big-endian bytes each.
+    /// unlike is_in_range, the empty signature is rejected too since only real signatures are encoded this way
+    ///
+    /// # Arguments
+    /// * `fixed` - A &[u8; 64] that holds the fixed width encoding
+    ///
+    /// # Returns
+    /// a Result<Signature, &str> which is the signature, or an error if r or s is outside of [1, n - 1]
+    ///
+    pub fn from_fixed(fixed: &[u8; 64]) -> Result<Self, &'static str> {
+        let signature: Signature = Signature {
+            r: BigInt::from_bytes_be(Sign::Plus, &fixed[..32]),
+            s: BigInt::from_bytes_be(Sign::Plus, &fixed[32..])
+        };
+
+        let n: BigInt = Secp256k1::new().n;
+        let range: Range<BigInt> = one()..n;
+
+        if !range.contains(&signature.r) || !range.contains(&signature.s) {
+            return Err("signature values are out of range");
+        }
+
+        Ok(signature)
+    }
+}
+
+/// parses a hex string into a BigInt only if it is written exactly the way Signature serializes it,
+/// lowercase and without leading zeros, so that a signature only has a single valid encoding
+/// 
+/// # Arguments
+/// * `hex` - A string slice that holds the hex to parse
+/// 
+/// # Returns
+/// An Option<BigInt> which is the parsed value, or None if the hex is invalid or not canonical
+/// 
+fn parse_canonical_hex(hex: &str) -> Option<BigInt> {
+    let value: BigInt = BigInt::parse_bytes(hex.as_bytes(), 16)?;
+
+    if format!("{:x}", value) != hex {
+        return None;
+    }
+
+    Some(value)
 }
 
 /// implement for serialization for Signature
@@ -49,7 +128,8 @@ impl Serialize for Signature {
 
 /// implement for deserialization for Signature
 /// manual implementation needed because BigInt is not directly deserializable
-/// implementation is done by deserializing the hex string as a bigint
+/// implementation is done by deserializing the hex string as a bigint.
+/// non-canonical hex and out of range values are rejected so that every node agrees on which signatures are valid
 impl<'de> Deserialize<'de> for Signature {
 
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -66,10 +146,16 @@ impl<'de> Deserialize<'de> for Signature {
 
         let fields: SignatureFields = SignatureFields::deserialize(deserializer)?;
 
-        Ok(Signature {
-            r: math::bigint(&fields.r),
-            s: math::bigint(&fields.s)
-        })
+        let signature: Signature = Signature {
+            r: parse_canonical_hex(&fields.r).ok_or_else(|| de::Error::custom("r is not canonical hex"))?,
+            s: parse_canonical_hex(&fields.s).ok_or_else(|| de::Error::custom("s is not canonical hex"))?
+        };
+
+        if !signature.is_in_range() {
+            return Err(de::Error::custom("signature values are out of range"));
+        }
+
+        Ok(signature)
     }
 }
 
@@ -97,7 +183,11 @@ pub fn sign(message: &str, d: BigInt, k: Option<BigInt>) -> Signature {
 
     let k: BigInt = k.unwrap_or(modulo(&entropy(), &secp256k1.p));
 
-    let p: Point = secp256k1.g.multiply(k.clone(), W, get_curve_precomputed_points());
+    // a nonce that can't be multiplied or that's a multiple of n has no inverse, a new one is drawn
+    let p: Point = match secp256k1.g.multiply(k.clone(), W, get_curve_precomputed_points()) {
+        Ok(p) => p,
+        Err(_) => return sign(message, d, None)
+    };
 
     let r: BigInt = modulo(&p.x, &secp256k1.p);
     if r == zero() {
@@ -106,9 +196,12 @@ pub fn sign(message: &str, d: BigInt, k: Option<BigInt>) -> Signature {
 
     let m: String = hash(message.to_owned() + &secp256k1.p.to_string());
 
-    let sigma: BigInt = modulo(&((&d * &r + bigint(&m)) * 
-                            modular_multiplicative_inverse(&secp256k1.n, k.clone(), None, None)), 
-                            &secp256k1.n);
+    let k_inverse: BigInt = match modular_multiplicative_inverse(&secp256k1.n, k.clone(), None, None) {
+        Ok(k_inverse) => k_inverse,
+        Err(_) => return sign(message, d, None)
+    };
+
+    let sigma: BigInt = modulo(&((&d * &r + bigint(&m)) * k_inverse), &secp256k1.n);
 
     if sigma == zero() {
         return sign(message, d, Some(k));
@@ -133,18 +226,152 @@ pub fn verify_signature(signature: &Signature, message: &str, public_key: Point)
 
     let z: BigInt = bigint(&hash(message.to_owned() + &secp256k1.p.to_string()));
 
-    let w: BigInt = modulo(&modular_multiplicative_inverse(&secp256k1.n, signature.s.clone(), None, None), 
-                            &secp256k1.n);
+    // an s without an inverse can't be a valid signature
+    let w: BigInt = match modular_multiplicative_inverse(&secp256k1.n, signature.s.clone(), None, None) {
+        Ok(s_inverse) => modulo(&s_inverse, &secp256k1.n),
+        Err(_) => return false
+    };
 
     let u1: BigInt = modulo(&(z * &w), &secp256k1.n);
     let u2: BigInt = modulo(&(&signature.r * &w), &secp256k1.n); 
 
-    let p1: Point = secp256k1.g.multiply(u1, W, get_curve_precomputed_points());
-    let public_key_precomp: Vec<Point> = super::point::precompute_points(public_key.clone(), W);
+    // neither can a signature whose points can't be calculated
+    let res: Result<Point, InverseError> = secp256k1.g.multiply(u1, W, get_curve_precomputed_points()).and_then(|p1| {
+        let public_key_precomp: Vec<Point> = super::point::precompute_points(public_key.clone(), W)?;
+        let p2: Point = public_key.multiply(u2.clone(), W, &public_key_precomp)?;
 
-    let p2: Point = public_key.multiply(u2.clone(), W, &public_key_precomp);
+        p1.add(&p2)
+    });
+
+    match res {
+        Ok(res) => res.x.eq(&signature.r),
+        Err(_) => false
+    }
+}
 
-    let res: Point = p1.add(&p2);
 
-    res.x.eq(&signature.r)
+/// verifies many signatures at once, each against its own message and public key
+/// 
+/// # Arguments
+/// * `items` - A slice of (message, signature, public key) tuples to verify
+/// 
+/// # Returns
+/// A boolean that is true if every signature is valid and false if any of them isn't
+/// 
+pub fn verify_batch(items: &[(String, Signature, Point)]) -> bool {
+    items.iter().all(|(message, signature, public_key)| {
+        verify_signature(signature, message, public_key.clone())
+    })
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed_item(message: &str, private_key: u64) -> (String, Signature, Point) {
+        let d: BigInt = BigInt::from(private_key);
+        let public_key: Point = Secp256k1::new().g.multiply(d.clone(), W, get_curve_precomputed_points()).unwrap();
+
+        (message.to_string(), sign(message, d, None), public_key)
+    }
+
+    #[test]
+    fn batch_of_valid_signatures_verifies() {
+        let items: Vec<(String, Signature, Point)> = vec![
+            signed_item("first", 11),
+            signed_item("second", 22),
+            signed_item("third", 33)
+        ];
+
+        assert!(verify_batch(&items));
+        assert!(verify_batch(&[]));
+    }
+
+    #[test]
+    fn batch_with_one_invalid_signature_fails() {
+        let mut items: Vec<(String, Signature, Point)> = vec![
+            signed_item("first", 11),
+            signed_item("second", 22),
+            signed_item("third", 33)
+        ];
+
+        // the second signature now signs a different message than the one it's checked against
+        items[1].0 = "tampered".to_string();
+
+        assert!(!verify_batch(&items));
+    }
+
+    /// the json of a signature with the given r and s, written as hex the way Signature serializes them
+    fn signature_json(r: &BigInt, s: &BigInt) -> String {
+        format!("{{\"r\":\"{:x}\",\"s\":\"{:x}\"}}", r, s)
+    }
+
+    #[test]
+    fn valid_signature_deserializes() {
+        let d: BigInt = BigInt::from(1234567);
+        let public_key: Point = Secp256k1::new().g.multiply(d.clone(), W, get_curve_precomputed_points()).unwrap();
+        let signature: Signature = sign("message", d, None);
+
+        let json: String = serde_json::to_string(&signature).unwrap();
+        assert_eq!(json, signature_json(&signature.r, &signature.s));
+
+        let deserialized: Signature = serde_json::from_str(&json).unwrap();
+        assert!(deserialized.is_in_range());
+        assert!(verify_signature(&deserialized, "message", public_key));
+    }
+
+    #[test]
+    fn s_out_of_range_is_rejected() {
+        let n: BigInt = Secp256k1::new().n;
+        let r: BigInt = BigInt::from(5);
+
+        for s in [zero(), n.clone(), n + 1] {
+            assert!(serde_json::from_str::<Signature>(&signature_json(&r, &s)).is_err());
+
+            let signature: Signature = Signature { r: r.clone(), s };
+            assert!(!signature.is_in_range());
+            assert!(Signature::from_fixed(&signature.to_fixed()).is_err());
+        }
+
+        // only the empty signature can have zeros
+        assert!(serde_json::from_str::<Signature>(&signature_json(&zero(), &zero())).is_ok());
+    }
+
+    #[test]
+    fn leading_zeros_are_rejected() {
+        assert!(serde_json::from_str::<Signature>("{\"r\":\"5\",\"s\":\"7\"}").is_ok());
+        assert!(serde_json::from_str::<Signature>("{\"r\":\"5\",\"s\":\"07\"}").is_err());
+        assert!(serde_json::from_str::<Signature>("{\"r\":\"5\",\"s\":\"7A\"}").is_err());
+    }
+
+    #[test]
+    fn fixed_encoding_round_trips() {
+        let d: BigInt = BigInt::from(1234567);
+        let public_key: Point = Secp256k1::new().g.multiply(d.clone(), W, get_curve_precomputed_points()).unwrap();
+        let signature: Signature = sign("message", d, None);
+
+        let fixed: [u8; 64] = signature.to_fixed();
+        let decoded: Signature = Signature::from_fixed(&fixed).unwrap();
+
+        assert_eq!(decoded.to_fixed(), fixed);
+        assert!(verify_signature(&decoded, "message", public_key));
+    }
+
+    #[test]
+    fn out_of_range_fixed_signature_is_rejected() {
+        let n: BigInt = Secp256k1::new().n;
+        let one: BigInt = BigInt::from(1);
+
+        // the empty signature is only valid for rewards, it never comes out of the fixed encoding
+        assert!(Signature::from_fixed(&[0; 64]).is_err());
+
+        for (r, s) in [(zero(), one.clone()), (one.clone(), zero()), (n.clone(), one.clone()), (one.clone(), n.clone())] {
+            let signature: Signature = Signature { r, s };
+            assert!(Signature::from_fixed(&signature.to_fixed()).is_err());
+        }
+
+        let highest: Signature = Signature { r: &n - 1, s: &n - 1 };
+        assert!(Signature::from_fixed(&highest.to_fixed()).is_ok());
+    }
+}
\ No newline at end of file
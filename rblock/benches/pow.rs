@@ -0,0 +1,47 @@
+use std::{hint::black_box, time::Instant};
+use rblock::Block;
+
+const BLOCKS: u32 = 10_000;
+const ROUNDS: u32 = 100;
+
+/// times Block::pow_valid_bytes against the nibble loop of Block::verify_difficulty on the same blocks,
+/// run with cargo bench -p rblock
+fn main() {
+    let genesis: Block = Block::new_genesis();
+
+    // a difficulty that about a fourth of the hashes satisfy, so neither check always stops at the first chunk
+    let blocks: Vec<Block> = (0..BLOCKS).map(|nonce| {
+        let mut block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+        block.set_difficulty(0xfffffff3);
+        block.set_nonce(nonce);
+        block
+    }).collect();
+
+    // verify_difficulty takes the hash by value, so like every caller it clones the hash
+    let start: Instant = Instant::now();
+    let mut nibble_valid: u32 = 0;
+    for _ in 0..ROUNDS {
+        for block in blocks.iter() {
+            if Block::verify_difficulty(black_box(block.get_hash()), black_box(block.get_difficulty())) {
+                nibble_valid += 1;
+            }
+        }
+    }
+    let nibble_nanos: f64 = start.elapsed().as_nanos() as f64 / (BLOCKS * ROUNDS) as f64;
+
+    let start: Instant = Instant::now();
+    let mut bytes_valid: u32 = 0;
+    for _ in 0..ROUNDS {
+        for block in blocks.iter() {
+            if black_box(block).pow_valid_bytes() {
+                bytes_valid += 1;
+            }
+        }
+    }
+    let bytes_nanos: f64 = start.elapsed().as_nanos() as f64 / (BLOCKS * ROUNDS) as f64;
+
+    assert_eq!(nibble_valid, bytes_valid);
+
+    println!("verify_difficulty: {:.1} ns per block", nibble_nanos);
+    println!("pow_valid_bytes:   {:.1} ns per block", bytes_nanos);
+}
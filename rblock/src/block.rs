@@ -1,7 +1,8 @@
 use core::fmt;
+use std::{collections::HashSet, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Mutex}, thread};
 use ecdsa::secp256k1::Point;
-use sha256::hash;
-use super::{functions, Transaction, TRANSACTION_LIMIT_PER_BLOCK};
+use sha256::{hash, hash_bytes};
+use super::{functions, BlockHeader, Transaction, GENESIS_DIFFICULTY, GENESIS_TIMESTAMP, NETWORK_MAGIC, REWARD, TRANSACTION_LIMIT_PER_BLOCK};
 use serde::{Serialize, Deserialize};
 
 /// A block in the blockchain
@@ -28,6 +29,10 @@ pub struct Block {
     /// The merkel root of the block
     merkel_root: String,
 
+    /// The number of transactions in the block, hashed along with the merkel root since the merkel root 
+    /// doesn't change when the last transaction is duplicated
+    transaction_count: u64,
+
     /// The transactions in the block, limit is at 5000 transactions
     transactions: Vec<Transaction> 
 }
@@ -35,28 +40,78 @@ pub struct Block {
 /// adds display for Block struct for easy printing
 impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\theight: {}\n\thash: {}\n\ttimestamp: {}\n\tprev_hash: {}\n\tnonce: {}\n\tdifficulty: {}\n\tmerkel root: {}", 
+        write!(f, "\theight: {}\n\thash: {}\n\ttimestamp: {}\n\tprev_hash: {}\n\tnonce: {}\n\tdifficulty: {}\n\tmerkel root: {}\n\ttransaction count: {}", 
             self.height, 
             self.hash,
             self.timestamp,
             self.prev_hash,
             self.nonce,
             self.difficulty,
-            self.merkel_root)
+            self.merkel_root,
+            self.transaction_count)
     }
 }
 
+/// The reasons an edit to a block or reading a block from bytes can fail
+#[derive(Debug, PartialEq)]
+pub enum BlockError {
+    /// The index is past the block's last transaction
+    IndexOutOfRange(usize),
+
+    /// The transaction is the miner's reward, which a block past genesis can't go without
+    RemovingReward,
+
+    /// The bytes aren't a block, with the reason the decoding failed
+    InvalidBytes(String),
+
+    /// The bytes end before the block does, more of them have to be read
+    UnexpectedEof,
+
+    /// The block has no reward for a tag to go on
+    MissingReward,
+
+    /// The previous block is at the highest height there can be, so there is no height for a block on top of it
+    HeightOverflow,
+
+    /// The block has more transactions than TRANSACTION_LIMIT_PER_BLOCK, with how many it has
+    TooManyTransactions(usize),
+
+    /// The block's transactions take more bytes than allowed, with how many they take
+    TooManyBytes(usize)
+}
+
+/// implement display for block errors for easy printing
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::IndexOutOfRange(index) => write!(f, "there is no transaction at index {}", index),
+            BlockError::RemovingReward => write!(f, "the reward transaction can't be removed"),
+            BlockError::InvalidBytes(reason) => write!(f, "bytes are not a block: {}", reason),
+            BlockError::UnexpectedEof => write!(f, "bytes end before the block does"),
+            BlockError::MissingReward => write!(f, "the block has no reward"),
+            BlockError::HeightOverflow => write!(f, "there is no height above u64::MAX"),
+            BlockError::TooManyTransactions(count) => write!(f, "{} is too many transactions, the limit is {}", count, TRANSACTION_LIMIT_PER_BLOCK),
+            BlockError::TooManyBytes(bytes) => write!(f, "transactions take {} bytes, more than the limit", bytes)
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
 impl Block {
-    /// creates and returns new genesis block
+    /// creates and returns new genesis block.
+    /// genesis has the easiest difficulty so it's exempt from proof of work, any hash satisfies it.
+    /// every field is fixed, so it's the same block with the same hash on every node
     pub fn new_genesis() -> Self {
         let mut genesis: Block = Block {
             height: 0,
             hash: "".to_owned(),
-            timestamp: functions::get_unix_time(),
+            timestamp: GENESIS_TIMESTAMP,
             nonce: 0, 
-            difficulty: 0xffffffff, 
+            difficulty: GENESIS_DIFFICULTY, 
             prev_hash: "".to_owned(),
             merkel_root: "".to_owned(),
+            transaction_count: 0,
             transactions: vec![]
         };
 
@@ -66,33 +121,42 @@ impl Block {
     }
 
     /// generates a new valid block who's transactions need to be verified and 
-    /// who's hash needs to be rehashed to fit difficulty standard
+    /// who's hash needs to be rehashed to fit difficulty standard.
+    /// the difficulty is the one retargeting gives from the previous block and the first block of the new block's retarget window
     /// 
     /// # Arguments
     /// * `prev_block` - A reference to the previous block
+    /// * `window_start_block` - A reference to the first block of the new block's retarget window, at get_retarget_window_start
     /// * `transactions` - A reference to a vector of transactions
     /// 
     /// # Returns
-    /// * A new block
+    /// * A Result<Block, BlockError> which is the new block, or an error if the previous block's height can't be incremented
     /// 
-    pub fn new(prev_block: &Block, transactions: &Vec<Transaction>) -> Self {
+    pub fn new(prev_block: &Block, window_start_block: &Block, transactions: &Vec<Transaction>) -> Result<Self, BlockError> {
+        let height: u64 = prev_block.height.checked_add(1).ok_or(BlockError::HeightOverflow)?;
+
+        // only None when prev_block is at u64::MAX, which was just ruled out
+        let difficulty: u32 = Block::get_supposed_difficulty(prev_block, window_start_block).ok_or(BlockError::HeightOverflow)?;
+
         let mut new_block: Block = Block {
-            height: prev_block.height + 1,
+            height,
             hash: String::from(""),
             timestamp: functions::get_unix_time(),
             nonce: 0,
-            difficulty: prev_block.difficulty,
+            difficulty,
             prev_hash: prev_block.hash.clone(),
             merkel_root: functions::get_merkel_root(transactions),
+            transaction_count: transactions.len() as u64,
             transactions: transactions.to_owned()
         };
 
         new_block.set_hash();
 
-        new_block
+        Ok(new_block)
     }
 
-    /// rewards miner only if another reward doesn't already exist
+    /// rewards miner only if another reward doesn't already exist.
+    /// the reward is REWARD plus the fees of the block's transactions
     /// pretty much obselete since you could just add it yourself when using 
     /// block::new in the transactions you pass
     /// 
@@ -103,6 +167,12 @@ impl Block {
     /// * `miner_address` - A reference to the miner's public key
     /// 
     pub fn reward_miner(&mut self, miner_address: &Point) {
+        // a reward to a point that isn't a public key could never be spent
+        if !miner_address.is_on_curve() {
+            eprintln!("The miner's address is not a point on the curve.");
+            return;
+        }
+
         // check if there is already a reward in the block
         for transaction in &self.transactions {
             if transaction.get_sender() == Point::identity() {
@@ -111,11 +181,42 @@ impl Block {
             }
         }
         
-        let reward_transaction: Transaction = Transaction::reward_transaction(miner_address);
+        // the miner gets the fees of every transaction on top of the reward
+        let reward_amount: f32 = REWARD + functions::get_total_fees(&self.transactions);
+        let reward_transaction: Transaction = Transaction::reward_transaction_with(miner_address, reward_amount, &[]);
         
-        self.transactions.push(reward_transaction);
+        // the reward is always the first transaction
+        self.transactions.insert(0, reward_transaction);
+        self.merkel_root = functions::get_merkel_root(&self.transactions);
+        self.transaction_count = self.transactions.len() as u64;
+        self.set_hash();
+    }
+
+    /// removes a transaction from the block, then recomputes the merkel root and hash.
+    /// used to take an invalid transaction out of a block that isn't mined yet, the block has to be mined again after
+    /// 
+    /// # Arguments
+    /// * `index` - The index of the transaction to remove
+    /// 
+    /// # Modifications
+    /// * Removes the transaction and changes the block's merkel root, transaction count and hash, hence the mut self
+    /// 
+    /// # Returns
+    /// * A Result<Transaction, BlockError> which is the removed transaction, or an error if the index is out of range or is the reward's
+    /// 
+    pub fn remove_transaction(&mut self, index: usize) -> Result<Transaction, BlockError> {
+        let transaction: &Transaction = self.transactions.get(index).ok_or(BlockError::IndexOutOfRange(index))?;
+
+        if transaction.get_sender() == Point::identity() {
+            return Err(BlockError::RemovingReward);
+        }
+
+        let removed: Transaction = self.transactions.remove(index);
         self.merkel_root = functions::get_merkel_root(&self.transactions);
+        self.transaction_count = self.transactions.len() as u64;
         self.set_hash();
+
+        Ok(removed)
     }
 
     /// sets the block's difficulty
@@ -132,6 +233,62 @@ impl Block {
         self.set_hash();
     }
 
+    /// recomputes the block's merkel root and hash on top of a given previous hash, starting from nonce 0.
+    /// used when the hashing scheme changed and every block of the chain has to be hashed again,
+    /// the block then has to be mined again to satisfy its difficulty
+    /// 
+    /// # Arguments
+    /// * `prev_hash` - The hash of the previous block, after it was itself rehashed
+    /// 
+    /// # Modifications
+    /// * Changes the block's previous hash, merkel root, transaction count, nonce and hash, hence the mut self
+    /// 
+    pub fn rehash(&mut self, prev_hash: &str) {
+        self.prev_hash = prev_hash.to_owned();
+        self.merkel_root = functions::get_merkel_root(&self.transactions);
+        self.transaction_count = self.transactions.len() as u64;
+        self.nonce = 0;
+        self.set_hash();
+    }
+
+    /// replaces the tag of the block's reward, then recomputes the merkel root and hash.
+    /// used with the tag a miner outside of the node changed along with the nonce to get more hashes to try
+    /// 
+    /// # Arguments
+    /// * `tag` - The new tag of the reward
+    /// 
+    /// # Modifications
+    /// * Changes the block's reward, merkel root and hash, hence the mut self
+    /// 
+    /// # Returns
+    /// * A Result<(), BlockError> which is Ok(()) if the reward was tagged, or an error if the block has no reward
+    /// 
+    pub fn set_reward_tag(&mut self, tag: &[u8]) -> Result<(), BlockError> {
+        // the reward is always the first transaction
+        let reward: &mut Transaction = self.transactions.first_mut()
+            .filter(|transaction| transaction.get_sender() == Point::identity())
+            .ok_or(BlockError::MissingReward)?;
+
+        *reward = Transaction::reward_transaction_with(&reward.get_recipient(), reward.get_amount(), tag);
+        self.merkel_root = functions::get_merkel_root(&self.transactions);
+        self.set_hash();
+
+        Ok(())
+    }
+
+    /// sets the block's nonce and generates hash, used with a nonce found by a miner outside of the node
+    /// 
+    /// # Arguments
+    /// * `nonce` - The new nonce
+    /// 
+    /// # Modifications
+    /// * Changes the block's nonce and hash, hence the mut self
+    /// 
+    pub fn set_nonce(&mut self, nonce: u32) {
+        self.nonce = nonce;
+        self.set_hash();
+    }
+
     /// increments nonce and generates hash
     /// 
     /// # Modifications
@@ -148,6 +305,81 @@ impl Block {
         self.set_hash();
     }
 
+    /// mines the block by searching for a nonce that makes the hash satisfy the difficulty.
+    /// the nonces are split between the threads, thread i trying nonces i, i + threads, i + 2 * threads...
+    /// and every thread stops as soon as one of them finds a solution. if no nonce works,
+    /// the timestamp is bumped by a second and the search starts over with new hashes
+    ///
+    /// # Arguments
+    /// * `threads` - The number of threads to search with, at least one is used
+    ///
+    /// # Modifications
+    /// * Changes the block's nonce and hash, and its timestamp if every nonce was tried, hence the mut self
+    ///
+    /// # Returns
+    /// * A u64 which is the number of hashes tried, to compute the hash rate
+    ///
+    pub fn mine(&mut self, threads: usize) -> u64 {
+        let threads: u64 = threads.max(1) as u64;
+        let mut hashes_tried: u64 = 0;
+
+        loop {
+            let found: AtomicBool = AtomicBool::new(false);
+            let tried: AtomicU64 = AtomicU64::new(0);
+            let solution: Mutex<Option<(u32, String)>> = Mutex::new(None);
+            let block: &Block = self;
+
+            thread::scope(|scope| {
+                for first_nonce in 0..threads {
+                    let (found, tried, solution) = (&found, &tried, &solution);
+
+                    scope.spawn(move || {
+                        // every thread reuses its own buffer for the message
+                        let mut message: Vec<u8> = Vec::new();
+                        let mut thread_tried: u64 = 0;
+                        let mut nonce: u64 = first_nonce;
+
+                        while nonce <= u32::MAX as u64 && !found.load(Ordering::Relaxed) {
+                            functions::write_block_message(&mut message,
+                                                           NETWORK_MAGIC,
+                                                           block.height,
+                                                           block.timestamp,
+                                                           &block.prev_hash,
+                                                           nonce as u32,
+                                                           block.difficulty,
+                                                           &block.merkel_root,
+                                                           block.transaction_count);
+                            let hash: String = hash_bytes(&message);
+                            thread_tried += 1;
+
+                            if Block::verify_difficulty(hash.clone(), block.difficulty) {
+                                found.store(true, Ordering::Relaxed);
+                                // unwrap since no thread panics while holding the lock
+                                *solution.lock().unwrap() = Some((nonce as u32, hash));
+                                break;
+                            }
+
+                            nonce += threads;
+                        }
+
+                        tried.fetch_add(thread_tried, Ordering::Relaxed);
+                    });
+                }
+            });
+
+            hashes_tried += tried.into_inner();
+
+            if let Some((nonce, hash)) = solution.into_inner().unwrap() {
+                self.nonce = nonce;
+                self.hash = hash;
+                return hashes_tried;
+            }
+
+            // every nonce was tried, a new timestamp gives new hashes to try
+            self.timestamp += 1;
+        }
+    }
+
     /// returns the current block's hash
     pub fn get_hash(&self) -> String {
         self.hash.clone()
@@ -168,6 +400,25 @@ impl Block {
         self.transactions.clone()
     }
 
+    /// returns the ids of the block's transactions, their hashes, in the order of the transactions
+    pub fn transaction_ids(&self) -> Vec<String> {
+        self.transactions.iter()
+            .map(|transaction| transaction.get_hash())
+            .collect()
+    }
+
+    /// returns the block's reward transaction, the one sent by Point::identity, if it has one
+    pub fn get_reward_transaction(&self) -> Option<Transaction> {
+        self.transactions.iter()
+            .find(|transaction| transaction.get_sender() == Point::identity())
+            .cloned()
+    }
+
+    /// returns the current block's nonce, the one that makes the hash satisfy the difficulty once mined
+    pub fn get_nonce(&self) -> u32 {
+        self.nonce
+    }
+
     /// returns the current block's difficulty
     pub fn get_difficulty(&self) -> u32 {
         self.difficulty.clone()
@@ -182,6 +433,68 @@ impl Block {
     pub fn get_height(&self) -> u64 {
         self.height.clone()
     }
+
+    /// returns the number of transactions the block claims to have, which is part of its hash
+    pub fn get_transaction_count(&self) -> u64 {
+        self.transaction_count
+    }
+
+    /// returns the current block's header, which is all of the block's data except its transactions
+    pub fn get_header(&self) -> BlockHeader {
+        BlockHeader {
+            height: self.height,
+            hash: self.hash.clone(),
+            timestamp: self.timestamp,
+            prev_hash: self.prev_hash.clone(),
+            nonce: self.nonce,
+            difficulty: self.difficulty,
+            merkel_root: self.merkel_root.clone(),
+            transaction_count: self.transaction_count
+        }
+    }
+
+    /// returns the block encoded as compact bytes to send to other nodes, the same encoding the db stores blocks with
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // unwrap since blocks are always serializable
+        bincode::serialize(self).unwrap()
+    }
+
+    /// reads a block from the bytes made by to_bytes.
+    /// the block isn't verified, but it has the same message and hash as the block that was encoded
+    /// 
+    /// # Arguments
+    /// * `bytes` - The encoded block
+    /// 
+    /// # Returns
+    /// * A Result<Block, BlockError> which is the decoded block, or an error if the bytes are cut off or aren't a block
+    /// 
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BlockError> {
+        bincode::deserialize(bytes).map_err(|e| match *e {
+            // a partial read off a socket, not corrupt data
+            bincode::ErrorKind::Io(ref io_error) if io_error.kind() == std::io::ErrorKind::UnexpectedEof => BlockError::UnexpectedEof,
+            _ => BlockError::InvalidBytes(e.to_string())
+        })
+    }
+ 
+    /// returns the block encoded by to_bytes as a single line of base64, to dump or share it as text
+    pub fn to_base64(&self) -> String {
+        functions::encode_base64(&self.to_bytes())
+    }
+
+    /// reads a block from the base64 made by to_base64
+    /// 
+    /// # Arguments
+    /// * `text` - The base64 encoded block
+    /// 
+    /// # Returns
+    /// * A Result<Block, BlockError> which is the decoded block, or an error if the text isn't base64 or isn't a block
+    /// 
+    pub fn from_base64(text: &str) -> Result<Self, BlockError> {
+        let bytes: Vec<u8> = functions::decode_base64(text)
+            .ok_or(BlockError::InvalidBytes("block is not base64".to_owned()))?;
+
+        Block::from_bytes(&bytes)
+    }
  
     /// Hashes with the data in the block and sets the hash 
     /// 
@@ -189,21 +502,111 @@ impl Block {
     /// * Changes the block's hash, hence the mut self
     /// 
     fn set_hash(&mut self) {
-        self.hash = hash(self.get_message());
+        self.hash = hash_bytes(&self.message_bytes());
+    }
+
+    /// hashes the block as a block of the network with the given magic instead of this one.
+    /// used to build a block for another network, which this network has to reject
+    /// 
+    /// # Arguments
+    /// * `network_magic` - The magic of the network to hash the block for
+    /// 
+    /// # Modifications
+    /// * Changes the block's hash, hence the mut self
+    /// 
+    pub fn set_hash_for_network(&mut self, network_magic: u32) {
+        self.hash = self.hash_for_network(network_magic);
+    }
+
+    /// returns the hash the block has on the network with the given magic, its hash is the one for NETWORK_MAGIC
+    /// 
+    /// # Arguments
+    /// * `network_magic` - The magic of the network to hash the block for
+    /// 
+    pub fn hash_for_network(&self, network_magic: u32) -> String {
+        let mut buf: Vec<u8> = Vec::new();
+        functions::write_block_message(&mut buf,
+                                       network_magic,
+                                       self.height, 
+                                       self.timestamp, 
+                                       &self.prev_hash, 
+                                       self.nonce, 
+                                       self.difficulty, 
+                                       &self.merkel_root,
+                                       self.transaction_count);
+
+        hash_bytes(&buf)
     }
 
     /// gets the message that was used to hash the block
     pub fn get_message(&self) -> String {
-        format!("{}{}{}{}{}{}", 
-                self.height, 
-                self.timestamp,
-                self.prev_hash,
-                self.nonce,
-                self.difficulty,
-                self.merkel_root)
+        functions::get_block_message(self.height, 
+                                     self.timestamp, 
+                                     &self.prev_hash, 
+                                     self.nonce, 
+                                     self.difficulty, 
+                                     &self.merkel_root,
+                                     self.transaction_count)
+    }
+
+    /// returns the bytes of the message that is hashed to get the block's hash,
+    /// which are the exact bytes of get_message without going through a String
+    pub fn message_bytes(&self) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+        self.write_message_bytes(&mut buf);
+
+        buf
+    }
+
+    /// writes the bytes of the message that is hashed to get the block's hash into buf.
+    /// lets a miner reuse the same buffer across nonce iterations
+    /// 
+    /// # Arguments
+    /// * `buf` - A &mut Vec<u8> which is cleared and then filled with the message
+    /// 
+    pub fn write_message_bytes(&self, buf: &mut Vec<u8>) {
+        functions::write_block_message(buf,
+                                       NETWORK_MAGIC,
+                                       self.height, 
+                                       self.timestamp, 
+                                       &self.prev_hash, 
+                                       self.nonce, 
+                                       self.difficulty, 
+                                       &self.merkel_root,
+                                     self.transaction_count);
+    }
+
+    /// returns an id for the block's content that doesn't depend on the nonce or the timestamp,
+    /// so the same transactions mined on top of the same block always share an id
+    pub fn content_id(&self) -> String {
+        hash(format!("{}{}{}", self.height, self.prev_hash, self.merkel_root))
+    }
+
+    /// checks if two blocks were made from the same template, which is when they only differ by
+    /// their nonce, timestamp and hash
+    ///
+    /// # Arguments
+    /// * `other` - A &Block which specifies the block to compare with
+    ///
+    /// # Returns
+    /// * True if both blocks have the same height, previous hash, merkel root and transactions, false otherwise
+    ///
+    pub fn same_content(&self, other: &Block) -> bool {
+        self.height == other.height
+            && self.prev_hash == other.prev_hash
+            && self.merkel_root == other.merkel_root
+            && self.transactions.len() == other.transactions.len()
+            && self.transactions.iter()
+                .zip(&other.transactions)
+                .all(|(transaction, other_transaction)| transaction.get_hash() == other_transaction.get_hash())
     }
 
-    /// verifies that the 4-bit sized chunks of the hash are within the correct value range
+    /// verifies that the 4-bit sized chunks of the hash are within the correct value range.
+    /// the last 8 hex characters of the hash are compared to the 8 chunks of the difficulty one by one,
+    /// and every chunk of the hash has to be at most the difficulty's chunk at the same position.
+    /// it's not a comparison of the numbers, a hash with a lower high chunk than the difficulty
+    /// still fails if any lower chunk is higher than the difficulty's, which is what get_passing_values counts.
+    /// a hash that isn't only hex characters or is shorter than 8 characters never satisfies a difficulty
     /// 
     /// # Arguments
     /// * `hash` - The hash to verify
@@ -215,7 +618,10 @@ impl Block {
     pub fn verify_difficulty(hash: String, difficulty: u32) -> bool {
 
         // get last 8 characters (4 bytes) of the hash to compare for difficulty rating
-        let hash_u32: u32 = u32::from_str_radix(&hash[hash.len() - 8..], 16).unwrap();
+        let hash_u32: u32 = match functions::get_hash_tail(&hash) {
+            Some(hash_u32) => hash_u32,
+            None => return false
+        };
 
         // half-byte per half-byte comparison
         for i in (0..=28).step_by(4) {
@@ -230,6 +636,129 @@ impl Block {
         true
     }
 
+    /// verifies that the block's hash satisfies its difficulty, with the same verdict as verify_difficulty for a 64 character hash.
+    /// the hash is decoded once and every 4-bit chunk is compared in a single subtraction instead of one branch per chunk:
+    /// each chunk gets its own byte, and a byte of 0x80 + difficulty chunk - hash chunk keeps its top bit only if the hash chunk isn't higher
+    /// 
+    /// # Returns
+    /// * True if the hash is within the difficulty rating, false otherwise or if the hash isn't 64 hex characters
+    /// 
+    pub fn pow_valid_bytes(&self) -> bool {
+        let hash_bytes: [u8; 32] = match functions::get_hash_bytes(&self.hash) {
+            Some(hash_bytes) => hash_bytes,
+            None => return false
+        };
+
+        // same last 4 bytes that verify_difficulty compares
+        let hash_u32: u32 = u32::from_be_bytes([hash_bytes[28], hash_bytes[29], hash_bytes[30], hash_bytes[31]]);
+
+        let top_bits: u64 = 0x8080808080808080;
+
+        // chunks are at most 0xf so no byte ever borrows from the next one
+        let compared: u64 = (functions::spread_chunks(self.difficulty) | top_bits) - functions::spread_chunks(hash_u32);
+
+        compared & top_bits == top_bits
+    }
+
+    /// returns how far the block's hash is from satisfying the block's difficulty.
+    /// if the hash doesn't satisfy it, it's the sum of how much each 4-bit chunk of the hash goes over
+    /// the difficulty's chunk. if it does, it's minus the room left under the difficulty's chunks, 
+    /// so the more negative the margin, the easier the hash passed. a malformed hash is as far as it gets
+    /// 
+    /// # Returns
+    /// * An i64 which is positive if the difficulty isn't satisfied and non-positive if it is
+    /// 
+    pub fn difficulty_margin(&self) -> i64 {
+        // same 4 bytes of the hash that verify_difficulty compares
+        let hash_u32: u32 = match functions::get_hash_tail(&self.hash) {
+            Some(hash_u32) => hash_u32,
+            None => return i64::MAX
+        };
+
+        let mut excess: i64 = 0;
+        let mut room: i64 = 0;
+
+        for i in (0..=28).step_by(4) {
+            let difficulty_bits: i64 = ((self.difficulty >> i) & 0xf) as i64;
+            let hash_bits: i64 = ((hash_u32 >> i) & 0xf) as i64;
+
+            if hash_bits > difficulty_bits {
+                excess += hash_bits - difficulty_bits;
+            } else {
+                room += difficulty_bits - hash_bits;
+            }
+        }
+
+        if excess > 0 {
+            excess
+        } else {
+            -room
+        }
+    }
+
+    /// returns the expected number of hashes needed to find one that satisfies the block's difficulty.
+    /// each 4-bit chunk of the hash has (difficulty chunk + 1) values out of 16 that pass, 
+    /// so the expected number of attempts is 16^8 over the product of those
+    /// 
+    /// # Returns
+    /// * A u128 which is the expected number of attempts, 1 for the easiest difficulty
+    /// 
+    pub fn expected_attempts(&self) -> u128 {
+        let passing_values: u128 = functions::get_passing_values(self.difficulty) as u128;
+        let total_values: u128 = 1 << 32;
+
+        // round to the nearest attempt
+        (total_values + passing_values / 2) / passing_values
+    }
+
+    /// returns the number of leading zero bits of the block's hash,
+    /// the usual way explorers show how much work a hash took
+    ///
+    /// # Returns
+    /// * A u32 which is the number of leading zero bits, counting up to the first non hex character
+    ///
+    pub fn leading_zero_bits(&self) -> u32 {
+        let mut zero_bits: u32 = 0;
+
+        for c in self.hash.chars() {
+            match c.to_digit(16) {
+                Some(0) => zero_bits += 4,
+                // only the top 4 bits of the u32 hold the nibble
+                Some(nibble) => return zero_bits + nibble.leading_zeros() - 28,
+                None => break,
+            }
+        }
+
+        zero_bits
+    }
+
+    /// checks that the block fits in both limits of a block, so a miner knows which one it went over.
+    /// the transaction count is checked against TRANSACTION_LIMIT_PER_BLOCK first, then the serialized bytes
+    /// of the transactions against a byte budget, the same bytes Mempool::take_best_bounded counts
+    /// 
+    /// # Arguments
+    /// * `max_bytes` - The most serialized bytes the block's transactions can take
+    /// 
+    /// # Returns
+    /// * Ok if the block is within both limits, or the limit it went over
+    /// 
+    pub fn check_limits(&self, max_bytes: usize) -> Result<(), BlockError> {
+        if self.transactions.len() > TRANSACTION_LIMIT_PER_BLOCK {
+            return Err(BlockError::TooManyTransactions(self.transactions.len()));
+        }
+
+        // unwrap since transactions always serialize
+        let transaction_bytes: usize = self.transactions.iter()
+            .map(|transaction| bincode::serialized_size(transaction).unwrap() as usize)
+            .sum();
+
+        if transaction_bytes > max_bytes {
+            return Err(BlockError::TooManyBytes(transaction_bytes));
+        }
+
+        Ok(())
+    }
+
     /// checks every transaction to make sure  that its good
     /// 
     /// # Returns
@@ -242,10 +771,49 @@ impl Block {
             return false;
         }
 
-        for transaction in &self.transactions {
+        // every block after genesis pays its miner, even a block without any other transaction
+        if self.height > 0 && self.get_reward_transaction().is_none() {
+            eprintln!("Block at height {} has no reward", self.height);
+            return false;
+        }
+
+        // the hashed transaction count has to be the real one
+        if self.transaction_count != self.transactions.len() as u64 {
+            eprintln!("Block claims {} transactions but has {}", self.transaction_count, self.transactions.len());
+            return false;
+        }
+
+        // merkel root duplicates the last transaction when there's an odd number of them, so a block with its
+        // last transactions duplicated would have the same root as the original block. no transaction can appear twice
+        let mut transaction_hashes: HashSet<String> = HashSet::new();
+
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            if !transaction_hashes.insert(transaction.get_hash()) {
+                eprintln!("A transaction appears more than once");
+                eprintln!("{}", transaction);
+                return false;
+            }
+
             // Point::identity is miner reward sender
-            if transaction.get_sender() != Point::identity() && !transaction.verify() {
-                eprintln!("A transaction is invalid");
+            if transaction.get_sender() == Point::identity() {
+                // the reward is always first, which also means there can't be more than one
+                if i != 0 {
+                    eprintln!("The reward has to be the first transaction of the block.");
+                    return false;
+                }
+
+                // a miner can't pay itself more than the reward and the fees of its block.
+                // the fees are summed the same way reward_miner sums them so both always agree
+                let reward_amount: f32 = REWARD + functions::get_total_fees(&self.transactions);
+
+                if transaction.get_amount() != reward_amount {
+                    eprintln!("Reward of {} should be {}", transaction.get_amount(), reward_amount);
+                    return false;
+                }
+            }
+
+            if let Err(e) = transaction.validate() {
+                eprintln!("A transaction is invalid: {}", e);
                 eprintln!("{}", transaction);
                 return false;
             }
@@ -254,6 +822,15 @@ impl Block {
         return true;
     }
 
+    /// verifies if the merkel root of the block is the one of its transactions.
+    /// the hash only covers the merkel root, so a transaction changed after the block was mined is only caught here
+    /// 
+    /// # Returns
+    /// * True if the merkel root matches the transactions, false otherwise
+    pub fn confirm_merkel_root(&self) -> bool {
+        self.merkel_root == functions::get_merkel_root(&self.transactions)
+    }
+
     /// verifies if the hash of the block fits with current data
     /// 
     /// # Returns
@@ -267,7 +844,7 @@ impl Block {
         Block::verify_difficulty(self.get_hash(), self.get_difficulty())
     }
 
-    /// returns the difficulty that a provided block should have.
+    /// returns the difficulty that the block on top of a provided block should have.
     /// 
     /// difficulty works like this: a u32 is set as FFFFFFFF
     /// -> each 4 bit chunk of that u32 is compared each of the last 8 4-bit chunks
@@ -275,58 +852,559 @@ impl Block {
     ///     4-bit chuck in the hash needs to take a value between 0 and F, an E between 0 and E,
     ///     a D between 0 and D, and so forth until its down to just zero.
 
-    ///     the difficulty is adjusted by slowly subtracting one the each 4-bit chunk of the difficulty u32
-    ///     until they are all 0
+    ///     the difficulty is adjusted every RETARGET_INTERVAL blocks from how long the last window of blocks took
+    ///     compared to BLOCK_SPEED, the blocks in between keep the difficulty of the block before them
     /// 
     /// # Arguments
-    /// * `base_block` - A &Block which specifies a reference to the block from which you want to know the difficulty
-    /// * `comp_block` - A &Block which specifies a reference to the block for which you want to know the correct difficulty
+    /// * `prev_block` - A &Block which specifies a reference to the block directly below the block you want to know the difficulty of
+    /// * `window_start_block` - A &Block which specifies a reference to the first block of the retarget window, at get_retarget_window_start
     /// 
     /// # Returns
-    /// A u32 which is the supposed difficulty of comp_block as a u32.
+    /// An Option<u32> which is the supposed difficulty of the next block as a u32, or None if prev_block is at the last possible height.
     /// 
-    pub fn get_supposed_difficulty(base_block: &Block, comp_block: &Block) -> u32 {
-        let latest_difficulty: u32 = base_block.get_difficulty();
-        // get time difference between blocks
-        let time_diff: u64 = comp_block.get_timestamp() - base_block.get_timestamp();
+    pub fn get_supposed_difficulty(prev_block: &Block, window_start_block: &Block) -> Option<u32> {
+        functions::get_retarget_difficulty(prev_block.get_difficulty(), 
+                                           prev_block.get_height(), 
+                                           prev_block.get_timestamp(), 
+                                           window_start_block.get_height(), 
+                                           window_start_block.get_timestamp())
+    }
+}
 
-        let mut difficulty: u32 = latest_difficulty;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecdsa::secp256k1::{get_curve_precomputed_points, Secp256k1, W};
+    use num_bigint::BigInt;
+    use crate::{BLOCK_SPEED, RETARGET_INTERVAL};
 
-        if time_diff > super::BLOCK_SPEED {
-            // reduce difficulty by increasing range of values per 4bit chuck
-            for i in (0..=28).rev().step_by(4) {
-                let mut bits: u32 = (latest_difficulty >> i) & 0xf;
+    const OTHER_NETWORK_MAGIC: u32 = 0x74657374; // "test"
 
-                // if current 4 bits and next 4 bits are 1111
-                if bits == 0xf { 
-                    continue;
-                }
+    fn key(n: u32) -> (Point, BigInt) {
+        let private_key: BigInt = BigInt::from(n) * BigInt::from(1234567u64) + BigInt::from(99);
+        (Secp256k1::new().g.multiply(private_key.clone(), W, get_curve_precomputed_points()).unwrap(), private_key)
+    }
 
-                // add one to the 4 bit block
-                bits += 1;
+    /// a block on top of genesis with the miner's reward first and then the transactions
+    fn rewarded_block(transactions: Vec<Transaction>) -> Block {
+        let genesis: Block = Block::new_genesis();
+        let mut block: Block = Block::new(&genesis, &genesis, &transactions).unwrap();
+        block.reward_miner(&key(9).0);
 
-                let mask: u32 = 0xffffffff & !(0xf << i); // use a mask to eliminate 4 bits that are changed
-                difficulty = (difficulty & mask) | (bits << i);
-                break;
-            }
-        } else {
-            // increase difficulty by reducing range of values per 4 bit chunk
-            for i in (0..=28).step_by(4) {
-                let mut bits: u32 = (latest_difficulty >> i) & 0xf;
+        block
+    }
 
-                if bits == 0 { 
-                    continue;
-                }
-                // sub one to the 4 bit block
-                bits -= 1;
-                
-                let mask: u32 = 0xffffffff & !(0xf << i); // use a mask to eliminate 4 bits that are changed
-                difficulty = (difficulty & mask) | (bits << i);
-                break;
+    #[test]
+    fn content_id_ignores_the_nonce() {
+        let genesis: Block = Block::new_genesis();
+        let block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+
+        let mut remined: Block = block.clone();
+        remined.increment_and_hash();
+
+        assert_eq!(remined.content_id(), block.content_id());
+        assert_ne!(remined.get_hash(), block.get_hash());
+
+        // a block on top of another block isn't the same content
+        let next: Block = Block::new(&block, &genesis, &vec![]).unwrap();
+        assert_ne!(next.content_id(), block.content_id());
+    }
+
+    #[test]
+    fn satisfied_difficulty_has_a_non_positive_margin() {
+        let genesis: Block = Block::new_genesis();
+        let mut block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+
+        assert!(block.difficulty_margin() <= 0);
+    }
+
+    #[test]
+    fn far_off_hash_has_a_large_positive_margin() {
+        let genesis: Block = Block::new_genesis();
+        let mut block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+        block.set_difficulty(0);
+
+        // every compared chunk of the hash is at least 8 over a difficulty of 0
+        while !block.get_hash()[block.get_hash().len() - 8..].chars().all(|c| c.to_digit(16).unwrap() >= 8) {
+            block.increment_and_hash();
+        }
+
+        assert!(block.difficulty_margin() >= 64);
+        assert!(!block.confirm_difficulty());
+    }
+
+    #[test]
+    fn duplicated_trailing_transactions_are_rejected() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a), Transaction::new(&a, &b, 2.0, &private_a)]);
+        assert!(block.confirm_transactions());
+
+        // an odd number of leaves duplicates the last one, so duplicating it for real gives the same root
+        let mut forged: Block = block.clone();
+        let last: Transaction = forged.transactions[2].clone();
+        forged.transactions.push(last);
+        forged.transaction_count = 4;
+        forged.set_hash();
+
+        assert_eq!(functions::get_merkel_root(&forged.transactions), block.merkel_root);
+        assert!(forged.confirm_hash());
+        assert!(!forged.confirm_transactions());
+    }
+
+    #[test]
+    fn expected_attempts_grow_with_the_difficulty() {
+        let genesis: Block = Block::new_genesis();
+        let mut block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+
+        block.set_difficulty(0xffffffff);
+        assert_eq!(block.expected_attempts(), 1);
+
+        // half the values pass in every chunk
+        block.set_difficulty(0x77777777);
+        assert_eq!(block.expected_attempts(), 1 << 8);
+
+        // a single value passes in every chunk, so every hash is tried on average
+        block.set_difficulty(0);
+        assert_eq!(block.expected_attempts(), 1 << 32);
+    }
+
+    #[test]
+    fn message_bytes_hash_like_the_message() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a)]);
+
+        assert_eq!(block.message_bytes(), block.get_message().into_bytes());
+        assert_eq!(hash_bytes(&block.message_bytes()), hash(block.get_message()));
+        assert_eq!(block.get_hash(), hash(block.get_message()));
+
+        // a reused buffer holds only the latest message
+        let mut buf: Vec<u8> = b"left over from a previous nonce".to_vec();
+        block.write_message_bytes(&mut buf);
+        assert_eq!(buf, block.message_bytes());
+    }
+
+    #[test]
+    fn leading_zero_bits_of_known_hashes() {
+        let mut block: Block = Block::new_genesis();
+
+        // 3 zero nibbles and then 0011
+        block.hash = "0003".to_owned() + &"f".repeat(60);
+        assert_eq!(block.leading_zero_bits(), 14);
+
+        block.hash = "1".to_owned() + &"0".repeat(63);
+        assert_eq!(block.leading_zero_bits(), 3);
+
+        block.hash = "f".repeat(64);
+        assert_eq!(block.leading_zero_bits(), 0);
+
+        block.hash = "0".repeat(64);
+        assert_eq!(block.leading_zero_bits(), 256);
+    }
+
+    #[test]
+    fn coinbase_has_to_be_the_first_transaction() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let genesis: Block = Block::new_genesis();
+        let transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        let reward: Transaction = Transaction::reward_transaction(&key(9).0);
+
+        let first: Block = Block::new(&genesis, &genesis, &vec![reward.clone(), transaction.clone()]).unwrap();
+        assert!(first.confirm_transactions());
+
+        let second: Block = Block::new(&genesis, &genesis, &vec![transaction, reward]).unwrap();
+        assert!(!second.confirm_transactions());
+
+        // reward_miner always puts it first
+        assert!(rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a)]).transactions[0].get_sender() == Point::identity());
+    }
+
+    #[test]
+    fn claimed_transaction_count_has_to_be_the_real_one() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a)]);
+        assert_eq!(block.get_transaction_count(), 2);
+
+        let mut miscounted: Block = block.clone();
+        miscounted.transaction_count = 1;
+        assert!(!miscounted.confirm_transactions());
+
+        // the count is hashed too, so it can't be changed without changing the hash
+        assert!(!miscounted.confirm_hash());
+        miscounted.set_hash();
+        assert_ne!(miscounted.get_hash(), block.get_hash());
+        assert!(!miscounted.confirm_transactions());
+    }
+
+    #[test]
+    fn non_genesis_block_needs_a_coinbase() {
+        let genesis: Block = Block::new_genesis();
+        assert!(genesis.confirm_transactions());
+
+        let empty: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+        assert!(!empty.confirm_transactions());
+
+        let only_coinbase: Block = rewarded_block(vec![]);
+        assert_eq!(only_coinbase.get_transactions().len(), 1);
+        assert!(only_coinbase.confirm_transactions());
+    }
+
+    #[test]
+    fn remined_template_has_the_same_content() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a)]);
+
+        let mut remined: Block = block.clone();
+        remined.increment_and_hash();
+        assert_ne!(remined.get_hash(), block.get_hash());
+        assert!(remined.same_content(&block));
+
+        // other transactions on top of the same block aren't the same template
+        let other: Block = rewarded_block(vec![Transaction::new(&a, &b, 2.0, &private_a)]);
+        assert!(!other.same_content(&block));
+    }
+
+    #[test]
+    fn mined_nonce_satisfies_the_difficulty_again() {
+        let mut block: Block = rewarded_block(vec![]);
+
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+
+        // a message rebuilt from the reported nonce hashes to the mined hash
+        let message: String = functions::get_block_message(block.get_height(), 
+                                                           block.get_timestamp(), 
+                                                           &block.get_prev_hash(), 
+                                                           block.get_nonce(), 
+                                                           block.get_difficulty(), 
+                                                           &block.get_merkel_root(),
+                                                           block.get_transaction_count());
+        assert_eq!(hash(message.clone()), block.get_hash());
+        assert!(Block::verify_difficulty(hash(message), block.get_difficulty()));
+        assert_eq!(block.get_header().get_nonce(), block.get_nonce());
+    }
+
+    #[test]
+    fn block_on_a_tip_is_one_height_above_it() {
+        let genesis: Block = Block::new_genesis();
+        assert_eq!(genesis.get_height(), 0);
+
+        let tip: Block = rewarded_block(vec![]);
+        let block: Block = Block::new(&tip, &genesis, &vec![]).unwrap();
+
+        assert_eq!(tip.get_height(), 1);
+        assert_eq!(block.get_height(), tip.get_height() + 1);
+        assert_eq!(block.get_header().get_height(), block.get_height());
+    }
+
+    #[test]
+    fn new_block_at_a_retarget_height_is_retargeted() {
+        let genesis: Block = Block::new_genesis();
+        let mut prev: Block = genesis.clone();
+        prev.height = RETARGET_INTERVAL - 1;
+        prev.difficulty = 0xfff00000;
+        prev.timestamp = genesis.get_timestamp() + (RETARGET_INTERVAL - 1) * BLOCK_SPEED / 4;
+
+        let block: Block = Block::new(&prev, &genesis, &vec![]).unwrap();
+        assert_eq!(block.get_height(), RETARGET_INTERVAL);
+        assert_eq!(block.get_difficulty(), 0xff300000);
+
+        // the block after keeps it
+        let next: Block = Block::new(&block, &genesis, &vec![]).unwrap();
+        assert_eq!(next.get_difficulty(), 0xff300000);
+
+        prev.height = u64::MAX;
+        assert!(matches!(Block::new(&prev, &genesis, &vec![]), Err(BlockError::HeightOverflow)));
+    }
+
+    #[test]
+    fn mined_block_satisfies_its_difficulty() {
+        let mut block: Block = rewarded_block(vec![]);
+        block.set_difficulty(0xffff0fff);
+        let unmined: Block = block.clone();
+
+        let hashes_tried: u64 = block.mine(4);
+
+        assert!(hashes_tried >= 1);
+        assert!(block.confirm_hash());
+        assert!(block.confirm_difficulty());
+        assert!(Block::verify_difficulty(block.get_hash(), block.get_difficulty()));
+
+        // only the nonce and hash change when a timestamp bump wasn't needed
+        assert!(block.same_content(&unmined));
+        assert_eq!(block.get_timestamp(), unmined.get_timestamp());
+    }
+
+    #[test]
+    fn removed_transaction_updates_the_merkel_root_and_hash() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let kept: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        let removed: Transaction = Transaction::new(&a, &b, 2.0, &private_a);
+
+        let mut block: Block = rewarded_block(vec![kept.clone(), removed.clone()]);
+        let expected: Block = rewarded_block(vec![kept]);
+
+        assert_eq!(block.remove_transaction(2).unwrap().get_hash(), removed.get_hash());
+        assert_eq!(block.get_transaction_count(), 2);
+        assert_eq!(block.get_merkel_root(), functions::get_merkel_root(&block.get_transactions()));
+        assert_eq!(block.get_merkel_root(), expected.get_merkel_root());
+        assert!(block.confirm_hash());
+        assert!(block.confirm_transactions());
+    }
+
+    #[test]
+    fn reward_and_out_of_range_transactions_are_not_removed() {
+        let mut block: Block = rewarded_block(vec![]);
+        let hash: String = block.get_hash();
+
+        assert!(matches!(block.remove_transaction(0), Err(BlockError::RemovingReward)));
+        assert!(matches!(block.remove_transaction(1), Err(BlockError::IndexOutOfRange(1))));
+        assert_eq!(block.get_transaction_count(), 1);
+        assert_eq!(block.get_hash(), hash);
+    }
+
+    #[test]
+    fn block_of_another_network_has_an_invalid_hash() {
+        let genesis: Block = Block::new_genesis();
+        let mut block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+        assert_eq!(block.hash_for_network(NETWORK_MAGIC), block.get_hash());
+        assert!(block.confirm_hash());
+
+        block.set_hash_for_network(OTHER_NETWORK_MAGIC);
+        assert_ne!(block.hash_for_network(NETWORK_MAGIC), block.get_hash());
+        assert!(!block.confirm_hash());
+    }
+
+    #[test]
+    fn transaction_ids_are_the_transaction_hashes() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a), Transaction::new(&a, &b, 2.0, &private_a)]);
+
+        let ids: Vec<String> = block.transaction_ids();
+        assert_eq!(ids.len() as u64, block.get_transaction_count());
+
+        // a block read back from json has no hash cached yet, it has to come out the same
+        let decoded: Block = serde_json::from_str(&serde_json::to_string(&block).unwrap()).unwrap();
+        for (i, transaction) in block.get_transactions().iter().enumerate() {
+            assert_eq!(ids[i], transaction.get_hash());
+            assert_eq!(ids[i], decoded.get_transactions()[i].get_hash());
+        }
+        assert_eq!(decoded.transaction_ids(), ids);
+    }
+
+    #[test]
+    fn reward_has_to_be_the_reward_and_the_fees() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let transactions: Vec<Transaction> = vec![
+            Transaction::new_with_fee(&a, &b, 1.0, 0.25, &private_a),
+            Transaction::new_with_fee(&a, &b, 2.0, 0.5, &private_a),
+        ];
+
+        let block: Block = rewarded_block(transactions.clone());
+        assert_eq!(block.get_reward_transaction().unwrap().get_amount(), REWARD + 0.75);
+        assert!(block.confirm_transactions());
+
+        // a block without any other transaction only pays the reward
+        let empty: Block = rewarded_block(vec![]);
+        assert_eq!(empty.get_reward_transaction().unwrap().get_amount(), REWARD);
+        assert!(empty.confirm_transactions());
+
+        let genesis: Block = Block::new_genesis();
+        for amount in [REWARD + 1.0, REWARD] {
+            let mut inflated: Vec<Transaction> = vec![Transaction::reward_transaction_with(&key(9).0, amount, &[])];
+            inflated.extend(transactions.clone());
+
+            let block: Block = Block::new(&genesis, &genesis, &inflated).unwrap();
+            assert!(!block.confirm_transactions());
+        }
+    }
+
+    #[test]
+    fn merkel_root_has_to_commit_to_the_coinbase() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a)]);
+        assert!(block.confirm_merkel_root());
+
+        // the root was computed without the coinbase that the transactions have
+        let mut without_coinbase_root: Block = block.clone();
+        without_coinbase_root.merkel_root = functions::get_merkel_root(&block.transactions[1..].to_vec());
+        without_coinbase_root.set_hash();
+        assert!(!without_coinbase_root.confirm_merkel_root());
+
+        // the root has the coinbase but the transactions don't
+        let mut without_coinbase: Block = block.clone();
+        without_coinbase.transactions.remove(0);
+        assert!(!without_coinbase.confirm_merkel_root());
+    }
+
+    #[test]
+    fn verify_difficulty_compares_every_chunk() {
+        let with_tail = |tail: &str| format!("{}{}", &hash("block".to_owned())[..56], tail);
+
+        assert!(Block::verify_difficulty(with_tail("ff300000"), 0xff300000));
+        assert!(Block::verify_difficulty(with_tail("ff200000"), 0xff300000));
+        assert!(!Block::verify_difficulty(with_tail("ff310000"), 0xff300000));
+        assert!(!Block::verify_difficulty(with_tail("ff400000"), 0xff300000));
+
+        // a higher high chunk isn't made up for by lower chunks under it, and the other way around
+        assert!(!Block::verify_difficulty(with_tail("1f000000"), 0x0fffffff));
+        assert!(!Block::verify_difficulty(with_tail("0e00000f"), 0x0ffffff0));
+        assert!(Block::verify_difficulty(with_tail("0ffffff0"), 0x0ffffff0));
+    }
+
+    #[test]
+    fn verify_difficulty_rejects_malformed_hashes() {
+        for malformed_hash in ["", "0000000", "0000000g", "+0000000", "-0000001", "00000000\u{e9}", "\u{e9}0000000"] {
+            assert!(!Block::verify_difficulty(malformed_hash.to_owned(), GENESIS_DIFFICULTY), "{}", malformed_hash);
+        }
+
+        // only the last 8 characters are compared, a shorter hash works as long as it's hex
+        assert!(Block::verify_difficulty("00000000".to_owned(), 0));
+
+        let mut block: Block = Block::new_genesis();
+        block.hash = "0000000g".to_owned();
+        assert_eq!(block.difficulty_margin(), i64::MAX);
+    }
+
+    #[test]
+    fn block_round_trips_through_bytes() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let mut block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a), Transaction::new(&b, &a, 0.5, &key(2).1)]);
+        block.set_difficulty(0x0fffffff);
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+
+        let bytes: Vec<u8> = block.to_bytes();
+        let decoded: Block = Block::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.get_hash(), block.get_hash());
+        assert_eq!(decoded.get_difficulty(), block.get_difficulty());
+        assert_eq!(decoded.get_message(), block.get_message());
+        assert!(decoded.confirm_hash() && decoded.confirm_difficulty() && decoded.confirm_transactions());
+
+        // no field names, quotes or separators around every value like in json
+        assert!(bytes.len() < serde_json::to_vec(&block).unwrap().len());
+
+        assert!(Block::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn cut_off_bytes_are_an_unexpected_eof() {
+        let bytes: Vec<u8> = rewarded_block(vec![]).to_bytes();
+        assert!(Block::from_bytes(&bytes).is_ok());
+
+        // cut off mid-block, more bytes have to be read
+        assert!(matches!(Block::from_bytes(&bytes[..bytes.len() / 2]), Err(BlockError::UnexpectedEof)));
+        assert!(matches!(Block::from_bytes(&bytes[..bytes.len() - 1]), Err(BlockError::UnexpectedEof)));
+        assert!(matches!(Block::from_bytes(&[]), Err(BlockError::UnexpectedEof)));
+
+        // corrupt data is a different error, the hash comes after the height and its length and isn't utf-8 anymore
+        let mut corrupt: Vec<u8> = bytes.clone();
+        corrupt[16] = 0xff;
+        assert!(matches!(Block::from_bytes(&corrupt), Err(BlockError::InvalidBytes(_))));
+    }
+
+    #[test]
+    fn block_round_trips_through_base64() {
+        let block: Block = rewarded_block(vec![]);
+        let text: String = block.to_base64();
+        assert!(!text.contains('\n'));
+
+        let decoded: Block = Block::from_base64(&text).unwrap();
+        assert_eq!(decoded.get_hash(), block.get_hash());
+        assert_eq!(decoded.to_bytes(), block.to_bytes());
+
+        assert!(matches!(Block::from_base64("not base64"), Err(BlockError::InvalidBytes(_))));
+    }
+
+    #[test]
+    fn block_hash_from_raw_fields_is_the_block_hash() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let block: Block = rewarded_block(vec![Transaction::new(&a, &b, 1.0, &private_a)]);
+
+        let hash: String = crate::block_hash(block.get_height(),
+                                             block.get_timestamp(),
+                                             &block.get_prev_hash(),
+                                             block.get_nonce(),
+                                             block.get_difficulty(),
+                                             &block.get_merkel_root(),
+                                             block.get_transactions().len() as u64);
+        assert_eq!(hash, block.get_hash());
+
+        // any other field gives another hash
+        let other: String = crate::block_hash(block.get_height(),
+                                              block.get_timestamp(),
+                                              &block.get_prev_hash(),
+                                              block.get_nonce() + 1,
+                                              block.get_difficulty(),
+                                              &block.get_merkel_root(),
+                                              block.get_transactions().len() as u64);
+        assert_ne!(other, block.get_hash());
+    }
+
+    #[test]
+    fn pow_valid_bytes_matches_verify_difficulty() {
+        let mut block: Block = Block::new_genesis();
+
+        for i in 0..2000u32 {
+            block.hash = hash(i.to_string());
+
+            // the hash of the next number gives a random difficulty, which is mostly too hard
+            let random_difficulty: u32 = functions::get_hash_tail(&hash((i + 1).to_string())).unwrap();
+
+            for difficulty in [GENESIS_DIFFICULTY, 0xfffffff3, 0xff300000, 0x0fffffff, 0, random_difficulty, random_difficulty | 0xeeeeeeee] {
+                block.difficulty = difficulty;
+                assert_eq!(block.pow_valid_bytes(), Block::verify_difficulty(block.hash.clone(), difficulty), "hash {} difficulty {:08x}", block.hash, difficulty);
             }
         }
+    }
+
+    #[test]
+    fn pow_valid_bytes_rejects_malformed_hashes() {
+        let mut block: Block = Block::new_genesis();
+        let valid_hash: String = hash("block".to_owned());
+
+        for malformed_hash in ["".to_owned(), valid_hash[1..].to_owned(), format!("{}0", valid_hash), format!("{}g", &valid_hash[1..])] {
+            block.hash = malformed_hash;
+            assert!(!block.pow_valid_bytes(), "{}", block.hash);
+        }
+    }
+
+    #[test]
+    fn check_limits_reports_the_limit_hit() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        let block: Block = rewarded_block(vec![transaction.clone(), transaction.clone()]);
+
+        let bytes: usize = block.get_transactions().iter()
+            .map(|transaction| bincode::serialized_size(transaction).unwrap() as usize)
+            .sum();
+        assert_eq!(block.check_limits(bytes), Ok(()));
+        assert_eq!(block.check_limits(bytes - 1), Err(BlockError::TooManyBytes(bytes)));
 
+        // the count is checked first, even if the bytes are over too
+        let mut crowded: Block = block.clone();
+        crowded.transactions = vec![transaction; TRANSACTION_LIMIT_PER_BLOCK + 1];
+        assert_eq!(crowded.check_limits(0), Err(BlockError::TooManyTransactions(TRANSACTION_LIMIT_PER_BLOCK + 1)));
 
-        difficulty
+        crowded.transactions.pop();
+        assert!(matches!(crowded.check_limits(0), Err(BlockError::TooManyBytes(_))));
+        assert_eq!(crowded.check_limits(usize::MAX), Ok(()));
     }
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+use super::functions;
+
+/// Something that tells the current unix time, so that code depending on the time can be given a fixed one
+pub trait Clock {
+    /// returns the current unix time in seconds
+    fn now(&self) -> u64;
+}
+
+/// The clock of the system, the time everything uses outside of tests
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        functions::get_unix_time()
+    }
+}
@@ -1,6 +1,6 @@
-use std::{collections::VecDeque, time::{Duration, SystemTime, UNIX_EPOCH}};
-use sha256::hash;
-use super::Transaction;
+use std::{collections::VecDeque, io::Write, time::{Duration, SystemTime, UNIX_EPOCH}};
+use sha256::{hash, hash_bytes};
+use super::{NonInclusionProof, SortedMerkleLeaf, Transaction, BLOCK_SPEED, MAX_RETARGET_FACTOR, NETWORK_MAGIC, RETARGET_INTERVAL, TRANSACTION_LIMIT_PER_BLOCK};
 
 /// returns the current unix time
 /// https://en.wikipedia.org/wiki/Unix_time
@@ -11,6 +11,143 @@ pub fn get_unix_time() -> u64 {
     duration_since_epoch.as_secs()
 }
 
+/// returns the most transactions per second the chain can process, 
+/// with every block full and mined exactly at the block speed
+pub fn get_max_tps() -> f64 {
+    TRANSACTION_LIMIT_PER_BLOCK as f64 / BLOCK_SPEED as f64
+}
+
+/// returns the expected share of blocks that get orphaned because another block was found while they were propagating.
+/// blocks are found at random, so the chance one is found in the propagation time is 1 - e^(-propagation / block time),
+/// which is about propagation / block time when propagation is much faster than blocks
+/// 
+/// # Arguments
+/// * `block_time` - The seconds between blocks, BLOCK_SPEED for this chain
+/// * `propagation_seconds` - The seconds it takes a block to reach the other miners
+/// 
+/// # Returns
+/// * A f64 between 0 and 1 representing the expected orphan rate
+/// 
+pub fn expected_orphan_rate(block_time: u64, propagation_seconds: f64) -> f64 {
+    1.0 - (-propagation_seconds / block_time as f64).exp()
+}
+
+/// returns how many bytes the chain grows by in a day, with blocks mined exactly at the block speed
+/// 
+/// # Arguments
+/// * `avg_block_bytes` - The average size of a block in bytes
+/// 
+/// # Returns
+/// * A u64 representing the estimated bytes added to the chain every day
+/// 
+pub fn get_daily_growth_bytes(avg_block_bytes: u64) -> u64 {
+    let seconds_per_day: u64 = 24 * 60 * 60;
+
+    (seconds_per_day / BLOCK_SPEED) * avg_block_bytes
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// returns bytes encoded as standard padded base64 text
+/// https://en.wikipedia.org/wiki/Base64
+/// 
+/// # Arguments
+/// * `bytes` - The bytes to encode
+/// 
+/// # Returns
+/// * A String of 4 characters for every 3 bytes, padded with '=' at the end
+/// 
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group: u32 = (chunk[0] as u32) << 16 
+                       | (*chunk.get(1).unwrap_or(&0) as u32) << 8 
+                       | *chunk.get(2).unwrap_or(&0) as u32;
+
+        // a chunk of n bytes fills n + 1 characters, the rest is padding
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// returns the bytes of standard padded base64 text made by encode_base64
+/// 
+/// # Arguments
+/// * `text` - The base64 text to decode
+/// 
+/// # Returns
+/// * An Option<Vec<u8>> of the decoded bytes, or None if the text isn't padded base64
+/// 
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text: &[u8] = text.as_bytes();
+
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut decoded: Vec<u8> = Vec::with_capacity(text.len() / 4 * 3);
+
+    for (i, chunk) in text.chunks(4).enumerate() {
+        // padding is only allowed at the end of the last chunk
+        let padding: usize = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && i != text.len() / 4 - 1) {
+            return None;
+        }
+
+        let mut group: u32 = 0;
+        for c in &chunk[..4 - padding] {
+            let value: usize = BASE64_ALPHABET.iter().position(|a| a == c)?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding as u32;
+
+        decoded.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+
+    Some(decoded)
+}
+
+/// returns the expected number of seconds to mine a block at a difficulty, with the same expected attempts as Block::expected_attempts
+/// 
+/// # Arguments
+/// * `difficulty` - The difficulty of the block
+/// * `hashrate` - The hashes per second of the miners
+/// 
+/// # Returns
+/// * A f64 representing the expected seconds before a hash satisfies the difficulty
+/// 
+pub fn estimate_block_time(difficulty: u32, hashrate: f64) -> f64 {
+    let total_values: f64 = (1u64 << 32) as f64;
+
+    total_values / get_passing_values(difficulty) as f64 / hashrate
+}
+
+/// returns the difficulty whose expected block time is the closest to a target for some hashrate,
+/// to set the starting difficulty of a private chain. difficulties only have a few steps, so the
+/// expected block time can be up to about twice as far from the target
+/// 
+/// # Arguments
+/// * `hashrate` - The hashes per second of the miners
+/// * `target_seconds` - The wanted seconds between blocks
+/// 
+/// # Returns
+/// * A u32 which is the difficulty rating, clamped between the hardest difficulty 0 and GENESIS_DIFFICULTY
+/// 
+pub fn target_difficulty(hashrate: f64, target_seconds: u64) -> u32 {
+    let total_values: f64 = (1u64 << 32) as f64;
+    let attempts: f64 = hashrate * target_seconds as f64;
+
+    get_difficulty_from_passing_values(total_values / attempts)
+}
+
 /// returns the merkel root of all the transactions
 /// https://en.wikipedia.org/wiki/Merkle_tree
 /// 
@@ -43,4 +180,756 @@ pub fn get_merkel_root(transactions: &Vec<Transaction>) -> String {
     }
 
     nodes[0].clone()
+}
+
+/// returns the sum of the fees of the transactions, which the miner gets on top of the reward.
+/// the reward itself never has a fee
+/// 
+/// # Arguments
+/// * `transactions` - A slice of transactions
+/// 
+/// # Returns
+/// * A f32 representing the total fees, 0 if there are no transactions other than the reward
+/// 
+pub fn get_total_fees(transactions: &[Transaction]) -> f32 {
+    transactions.iter()
+        .map(|transaction| transaction.get_fee())
+        .sum()
+}
+
+/// returns the proof that a transaction is part of the merkel root, the sibling hashes from the transaction up to the root.
+/// the nodes are paired the exact same way get_merkel_root pairs them, so a node left over at the end of
+/// one level is paired with the first node of the next level
+/// 
+/// # Arguments
+/// * `transactions` - A slice of transactions
+/// * `index` - The index of the transaction to prove
+/// 
+/// # Returns
+/// * A vector of (sibling hash, is the sibling on the right) pairs, empty if there is no transaction at the index
+/// 
+pub fn get_merkle_proof(transactions: &[Transaction], index: usize) -> Vec<(String, bool)> {
+    let mut proof: Vec<(String, bool)> = Vec::new();
+
+    if index >= transactions.len() {
+        return proof;
+    }
+
+    // every node is kept along with whether the transaction being proven is under it
+    let mut nodes: VecDeque<(String, bool)> = VecDeque::new();
+    for (i, transaction) in transactions.iter().enumerate() {
+        nodes.push_back((transaction.get_hash(), i == index));
+    }
+
+    // duplicate last element if odd number of leaves, the copy isn't the transaction being proven
+    if nodes.len() % 2 == 1 {
+        nodes.push_back((nodes[nodes.len() - 1].0.clone(), false));
+    }
+
+    while nodes.len() > 1 {
+        let (f, f_proven): (String, bool) = nodes.pop_front().unwrap_or_default();
+        let (s, s_proven): (String, bool) = nodes.pop_front().unwrap_or_default();
+
+        if f_proven {
+            proof.push((s.clone(), true));
+        } else if s_proven {
+            proof.push((f.clone(), false));
+        }
+
+        nodes.push_back((hash(f + &s), f_proven || s_proven));
+    }
+
+    proof
+}
+
+/// verifies a merkel proof made by get_merkle_proof, by hashing the transaction's hash 
+/// with every sibling in order and comparing the result with the merkel root
+/// 
+/// # Arguments
+/// * `tx_hash` - The hash of the transaction to prove
+/// * `proof` - The (sibling hash, is the sibling on the right) pairs from the transaction up to the root
+/// * `root` - The merkel root of the block the transaction should be in
+/// 
+/// # Returns
+/// * A bool that is true if the proof leads from the transaction to the root
+/// 
+pub fn verify_merkle_proof(tx_hash: &str, proof: &[(String, bool)], root: &str) -> bool {
+    // even a single transaction is hashed with its duplicate, so a transaction hash is never a root
+    if proof.is_empty() {
+        return false;
+    }
+
+    let mut node: String = tx_hash.to_owned();
+
+    for (sibling, sibling_on_right) in proof {
+        node = if *sibling_on_right {
+            hash(node + sibling)
+        } else {
+            hash(sibling.to_owned() + &node)
+        };
+    }
+
+    node == root
+}
+
+/// returns the merkel root of transaction ids in the sorted mode, where the ids are sorted before building the tree.
+/// unlike get_merkel_root, every level of the tree is paired on its own and duplicates its last node when it's odd,
+/// so the position of an id in the tree is given by its index, which is what allows proving that an id isn't in the tree.
+/// leaves and inner nodes are hashed with different prefixes and the number of leaves is hashed into the root,
+/// so a proof can't pass an inner node off as a leaf or claim another shape for the tree
+/// 
+/// # Arguments
+/// * `txids` - A slice of transaction ids, in any order
+/// 
+/// # Returns
+/// * A string representing the sorted merkel root, empty if there are no transaction ids
+/// 
+pub fn get_sorted_merkle_root(txids: &[String]) -> String {
+    let leaves: Vec<String> = get_sorted_leaves(txids);
+
+    if leaves.is_empty() {
+        return "".to_owned();
+    }
+
+    let mut level: Vec<String> = leaves.iter().map(|txid| hash_sorted_leaf(txid)).collect();
+    while level.len() > 1 {
+        level = get_next_sorted_level(&level);
+    }
+
+    bind_leaf_count(&level[0], leaves.len())
+}
+
+/// returns the proof that a transaction id isn't in the sorted merkel tree of the transaction ids
+/// 
+/// # Arguments
+/// * `txids` - A slice of transaction ids, in any order
+/// * `target` - The transaction id to prove the absence of
+/// 
+/// # Returns
+/// * An Option<NonInclusionProof> which is the proof, or None if the target is one of the transaction ids
+/// 
+pub fn merkle_non_inclusion_proof(txids: &[String], target: &str) -> Option<NonInclusionProof> {
+    let leaves: Vec<String> = get_sorted_leaves(txids);
+
+    // index of the first id that isn't below the target
+    let position: usize = leaves.partition_point(|txid| txid.as_str() < target);
+
+    if leaves.get(position).is_some_and(|txid| txid == target) {
+        return None;
+    }
+
+    let get_leaf = |index: usize| SortedMerkleLeaf {
+        index,
+        txid: leaves[index].clone(),
+        siblings: get_sorted_merkle_siblings(&leaves, index)
+    };
+
+    Some(NonInclusionProof {
+        leaf_count: leaves.len(),
+        left: position.checked_sub(1).map(get_leaf),
+        right: (position < leaves.len()).then(|| get_leaf(position))
+    })
+}
+
+/// verifies a proof made by merkle_non_inclusion_proof, by checking that both neighbours are in the tree, 
+/// are next to each other and are on each side of the target. the tree is trusted to be sorted, 
+/// which the root alone can't show
+/// 
+/// # Arguments
+/// * `target` - The transaction id that should be missing
+/// * `proof` - A reference to the non inclusion proof
+/// * `root` - The sorted merkel root the transaction id should be missing from
+/// 
+/// # Returns
+/// * A bool that is true if the proof shows the target isn't in the tree
+/// 
+pub fn verify_merkle_non_inclusion_proof(target: &str, proof: &NonInclusionProof, root: &str) -> bool {
+    let leaf_is_in_tree = |leaf: &SortedMerkleLeaf| {
+        get_root_from_siblings(&leaf.txid, leaf.index, proof.leaf_count, &leaf.siblings).is_some_and(|leaf_root| leaf_root == root)
+    };
+
+    match (&proof.left, &proof.right) {
+        // only an empty tree has no ids around the target
+        (None, None) => proof.leaf_count == 0 && root.is_empty(),
+        (None, Some(right)) => {
+            right.index == 0 && target < right.txid.as_str() && leaf_is_in_tree(right)
+        },
+        (Some(left), None) => {
+            left.index + 1 == proof.leaf_count && left.txid.as_str() < target && leaf_is_in_tree(left)
+        },
+        (Some(left), Some(right)) => {
+            left.index + 1 == right.index 
+                && left.txid.as_str() < target && target < right.txid.as_str()
+                && leaf_is_in_tree(left) && leaf_is_in_tree(right)
+        }
+    }
+}
+
+/// returns the leaves of a sorted merkel tree, the sorted transaction ids without duplicates
+fn get_sorted_leaves(txids: &[String]) -> Vec<String> {
+    let mut leaves: Vec<String> = txids.to_vec();
+    leaves.sort();
+    leaves.dedup();
+
+    leaves
+}
+
+/// returns the node of a transaction id at the bottom of a sorted merkel tree
+fn hash_sorted_leaf(txid: &str) -> String {
+    hash("00".to_owned() + txid)
+}
+
+/// returns the node above two nodes of a sorted merkel tree, with another prefix than leaves so the two can't be mixed up
+fn hash_sorted_node(left: &str, right: &str) -> String {
+    hash("01".to_owned() + left + right)
+}
+
+/// returns the root of a sorted merkel tree from the node at its top and its number of leaves
+fn bind_leaf_count(top: &str, leaf_count: usize) -> String {
+    hash(format!("{:016x}{}", leaf_count, top))
+}
+
+/// returns the level above a level of a sorted merkel tree, the last node is paired with itself if the level is odd
+fn get_next_sorted_level(level: &[String]) -> Vec<String> {
+    level.chunks(2)
+        .map(|pair| hash_sorted_node(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+/// returns the sibling hashes from a leaf of a sorted merkel tree up to the top node
+fn get_sorted_merkle_siblings(leaves: &[String], mut index: usize) -> Vec<String> {
+    let mut siblings: Vec<String> = Vec::new();
+    let mut level: Vec<String> = leaves.iter().map(|txid| hash_sorted_leaf(txid)).collect();
+
+    while level.len() > 1 {
+        // the last node of an odd level is its own sibling
+        let sibling_index: usize = if index.is_multiple_of(2) { (index + 1).min(level.len() - 1) } else { index - 1 };
+        siblings.push(level[sibling_index].clone());
+
+        level = get_next_sorted_level(&level);
+        index /= 2;
+    }
+
+    siblings
+}
+
+/// returns the root a leaf of a sorted merkel tree leads to with its siblings, following the side given by its index
+/// 
+/// # Returns
+/// * An Option<String> which is the root, or None if the siblings don't fit a tree of leaf_count leaves
+/// 
+fn get_root_from_siblings(txid: &str, mut index: usize, leaf_count: usize, siblings: &[String]) -> Option<String> {
+    if index >= leaf_count {
+        return None;
+    }
+
+    let mut node: String = hash_sorted_leaf(txid);
+    let mut level_len: usize = leaf_count;
+
+    for sibling in siblings {
+        if level_len == 1 {
+            return None;
+        }
+
+        if index.is_multiple_of(2) {
+            // a duplicated node has to be paired with itself, otherwise the leaf count would be a lie
+            if index == level_len - 1 && *sibling != node {
+                return None;
+            }
+
+            node = hash_sorted_node(&node, sibling);
+        } else {
+            node = hash_sorted_node(sibling, &node);
+        }
+
+        index /= 2;
+        level_len = level_len.div_ceil(2);
+    }
+
+    // the siblings have to reach the top of the tree
+    if level_len != 1 {
+        return None;
+    }
+
+    Some(bind_leaf_count(&node, leaf_count))
+}
+
+/// returns the message that is hashed to get a block's hash.
+/// shared by blocks and block headers so that both always hash the exact same data
+/// 
+/// # Arguments
+/// * `height` - The height of the block
+/// * `timestamp` - The timestamp of the block
+/// * `prev_hash` - The hash of the previous block
+/// * `nonce` - The nonce of the block
+/// * `difficulty` - The difficulty rating of the block
+/// * `merkel_root` - The merkel root of the block's transactions
+/// * `transaction_count` - The number of transactions in the block
+/// 
+/// # Returns
+/// * A string representing the message to hash
+/// 
+pub(crate) fn get_block_message(height: u64, timestamp: u64, prev_hash: &str, nonce: u32, difficulty: u32, merkel_root: &str, transaction_count: u64) -> String {
+    let mut buf: Vec<u8> = Vec::new();
+    write_block_message(&mut buf, NETWORK_MAGIC, height, timestamp, prev_hash, nonce, difficulty, merkel_root, transaction_count);
+
+    // only numbers and strs were written so the bytes are valid utf8
+    String::from_utf8(buf).unwrap()
+}
+
+/// returns the hash a block with these fields has, without building the block.
+/// lets a mining pool check a candidate from the raw header fields, it's always the same as Block::get_hash
+/// 
+/// # Arguments
+/// * `height` - The height of the block
+/// * `timestamp` - The timestamp of the block
+/// * `prev_hash` - The hash of the previous block
+/// * `nonce` - The nonce of the block
+/// * `difficulty` - The difficulty rating of the block
+/// * `merkel_root` - The merkel root of the block's transactions
+/// * `transaction_count` - The number of transactions in the block, the message hashes it too
+/// 
+/// # Returns
+/// * A string representing the hash of the block
+/// 
+pub fn block_hash(height: u64, timestamp: u64, prev_hash: &str, nonce: u32, difficulty: u32, merkel_root: &str, transaction_count: u64) -> String {
+    let mut buf: Vec<u8> = Vec::new();
+    write_block_message(&mut buf, NETWORK_MAGIC, height, timestamp, prev_hash, nonce, difficulty, merkel_root, transaction_count);
+
+    hash_bytes(&buf)
+}
+
+/// writes the bytes of the message that is hashed to get a block's hash into buf, 
+/// so that a buffer can be reused instead of allocating a new message for every hash.
+/// the message starts with the network magic so that a block from another network never has a valid hash here
+/// 
+/// # Arguments
+/// * `buf` - A &mut Vec<u8> which is cleared and then filled with the message
+/// * `network_magic` - The magic of the network the block is for, NETWORK_MAGIC for this one
+/// * `height` - The height of the block
+/// * `timestamp` - The timestamp of the block
+/// * `prev_hash` - The hash of the previous block
+/// * `nonce` - The nonce of the block
+/// * `difficulty` - The difficulty rating of the block
+/// * `merkel_root` - The merkel root of the block's transactions
+/// * `transaction_count` - The number of transactions in the block
+/// 
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_block_message(buf: &mut Vec<u8>, network_magic: u32, height: u64, timestamp: u64, prev_hash: &str, nonce: u32, difficulty: u32, merkel_root: &str, transaction_count: u64) {
+    buf.clear();
+
+    // writing to a vec can't fail
+    write!(buf, "{:08x}{}{}{}{}{}{}{}", 
+           network_magic,
+           height, 
+           timestamp,
+           prev_hash,
+           nonce,
+           difficulty,
+           merkel_root,
+           transaction_count).unwrap();
+}
+
+/// returns the last 8 hex characters of a hash as a u32, the part of the hash that is compared to the difficulty
+/// 
+/// # Arguments
+/// * `hash` - The hash, which has to be only hex characters
+/// 
+/// # Returns
+/// * An Option<u32> of the last 4 bytes of the hash, None if the hash is shorter than 8 characters or isn't hex
+/// 
+pub(crate) fn get_hash_tail(hash: &str) -> Option<u32> {
+    // from_str_radix would also take a sign, so every character is checked first
+    if hash.len() < 8 || !hash.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(&hash[hash.len() - 8..], 16).ok()
+}
+
+/// returns the 32 bytes of a sha256 hash written as 64 hex characters
+/// 
+/// # Arguments
+/// * `hash` - The hash as hex characters
+/// 
+/// # Returns
+/// * An Option<[u8; 32]> of the hash's bytes, or None if the hash isn't 64 hex characters
+/// 
+pub(crate) fn get_hash_bytes(hash: &str) -> Option<[u8; 32]> {
+    if hash.len() != 64 {
+        return None;
+    }
+
+    let mut bytes: [u8; 32] = [0; 32];
+    for (i, pair) in hash.as_bytes().chunks(2).enumerate() {
+        let high: u32 = (pair[0] as char).to_digit(16)?;
+        let low: u32 = (pair[1] as char).to_digit(16)?;
+
+        bytes[i] = (high << 4 | low) as u8;
+    }
+
+    Some(bytes)
+}
+
+/// returns the 8 4-bit chunks of value spread out to one per byte, the lowest chunk in the lowest byte,
+/// so that all the chunks can be compared at once with u64 arithmetic
+/// 
+/// # Arguments
+/// * `value` - The value to spread out
+/// 
+/// # Returns
+/// * A u64 where byte i is the chunk i of value
+/// 
+pub(crate) fn spread_chunks(value: u32) -> u64 {
+    let mut spread: u64 = value as u64;
+
+    spread = (spread | spread << 16) & 0x0000ffff0000ffff;
+    spread = (spread | spread << 8) & 0x00ff00ff00ff00ff;
+    spread = (spread | spread << 4) & 0x0f0f0f0f0f0f0f0f;
+
+    spread
+}
+
+/// returns how many of the 16^8 possible values of the hash's last 8 hex characters satisfy a difficulty.
+/// each 4-bit chunk of the hash has (difficulty chunk + 1) values that pass
+/// 
+/// # Arguments
+/// * `difficulty` - The difficulty rating
+/// 
+/// # Returns
+/// * A u64 which is the number of passing values, 1 for the hardest difficulty and 16^8 for the easiest
+/// 
+pub(crate) fn get_passing_values(difficulty: u32) -> u64 {
+    let mut passing_values: u64 = 1;
+
+    for i in (0..=28).step_by(4) {
+        passing_values *= (((difficulty >> i) & 0xf) + 1) as u64;
+    }
+
+    passing_values
+}
+
+/// returns the difficulty with the closest number of passing values to the given one.
+/// difficulties are built the way they have always been adjusted: 4-bit chunks go down to 0 starting 
+/// from the lowest one, so the top chunks are F, one chunk is in between and the chunks under it are 0.
+/// k chunks at F above a chunk m gives (m + 1) * 16^k passing values
+/// 
+/// # Arguments
+/// * `passing_values` - The number of passing values wanted, clamped between 1 and 16^8
+/// 
+/// # Returns
+/// * A u32 which is the difficulty rating
+/// 
+pub(crate) fn get_difficulty_from_passing_values(passing_values: f64) -> u32 {
+    let passing_values: f64 = passing_values.clamp(1.0, (1u64 << 32) as f64);
+
+    let mut full_chunks: u32 = (passing_values.log(16.0).floor() as u32).min(8);
+    if full_chunks == 8 {
+        return 0xffffffff;
+    }
+
+    let mut middle_values: u32 = (passing_values / 16f64.powi(full_chunks as i32)).round() as u32;
+
+    // rounded up to the next power of 16, or log was slightly under an exact power of 16
+    if middle_values >= 16 {
+        full_chunks += 1;
+        middle_values = 1;
+
+        if full_chunks == 8 {
+            return 0xffffffff;
+        }
+    }
+
+    let full_mask: u32 = if full_chunks == 0 { 0 } else { u32::MAX << (4 * (8 - full_chunks)) };
+
+    full_mask | ((middle_values - 1) << (4 * (7 - full_chunks)))
+}
+
+/// returns whether the block at height retargets the difficulty, which is every RETARGET_INTERVAL blocks after genesis
+pub(crate) fn is_retarget_height(height: u64) -> bool {
+    height >= RETARGET_INTERVAL && height.is_multiple_of(RETARGET_INTERVAL)
+}
+
+/// returns the height of the first block of the retarget window used for the difficulty of the block at height.
+/// below the first window it's genesis
+/// 
+/// # Arguments
+/// * `height` - The height of the block for which the difficulty is computed
+/// 
+/// # Returns
+/// * A u64 which is the height of the first block of the window
+/// 
+pub fn get_retarget_window_start(height: u64) -> u64 {
+    height.saturating_sub(RETARGET_INTERVAL)
+}
+
+/// returns the difficulty a block should have based on the block before it and the first block of its retarget window.
+/// only blocks at a multiple of RETARGET_INTERVAL retarget, every other block keeps the difficulty of the block before it.
+/// at a retarget, the time the window actually took is compared to the time it should have taken at BLOCK_SPEED
+/// and the number of passing values is scaled by it, by at most MAX_RETARGET_FACTOR either way.
+/// shared by blocks and block headers so that both retarget the exact same way
+/// 
+/// # Arguments
+/// * `prev_difficulty` - The difficulty of the block before
+/// * `prev_height` - The height of the block before
+/// * `prev_timestamp` - The timestamp of the block before
+/// * `window_start_height` - The height of the first block of the window
+/// * `window_start_timestamp` - The timestamp of the first block of the window
+/// 
+/// # Returns
+/// * A u32 which is the supposed difficulty, None if there can't be a block after the last possible height
+/// 
+pub(crate) fn get_retarget_difficulty(prev_difficulty: u32, prev_height: u64, prev_timestamp: u64, window_start_height: u64, window_start_timestamp: u64) -> Option<u32> {
+    // the heights can come from untrusted blocks
+    let height: u64 = prev_height.checked_add(1)?;
+
+    // genesis and chains shorter than a window keep the previous difficulty
+    if !is_retarget_height(height) || prev_height <= window_start_height {
+        return Some(prev_difficulty);
+    }
+
+    let expected_time: f64 = ((prev_height - window_start_height) * BLOCK_SPEED) as f64;
+
+    // saturating since a block could have a timestamp before the first block of the window
+    let actual_time: f64 = prev_timestamp.saturating_sub(window_start_timestamp) as f64;
+
+    // blocks that came too fast lower the number of passing values, so the difficulty is harder
+    let factor: f64 = (actual_time / expected_time).clamp(1.0 / MAX_RETARGET_FACTOR, MAX_RETARGET_FACTOR);
+
+    Some(get_difficulty_from_passing_values(get_passing_values(prev_difficulty) as f64 * factor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecdsa::secp256k1::{Point, Secp256k1};
+
+    #[test]
+    fn max_tps_is_a_full_block_per_block_speed() {
+        // 5000 transactions every 1200 seconds
+        assert!((get_max_tps() - 5000.0 / 1200.0).abs() < 1e-9);
+        assert!((get_max_tps() - 4.17).abs() < 0.01);
+    }
+
+    #[test]
+    fn daily_growth_is_a_block_every_block_speed() {
+        // 72 blocks a day at 1200 seconds each
+        assert_eq!(get_daily_growth_bytes(500_000), 72 * 500_000);
+        assert_eq!(get_daily_growth_bytes(0), 0);
+    }
+
+    const START: u64 = 1712016000;
+
+    /// the difficulty of the block after the one at RETARGET_INTERVAL - 1, with the window taking the given time
+    fn retarget_after(difficulty: u32, window_time: u64) -> Option<u32> {
+        get_retarget_difficulty(difficulty, RETARGET_INTERVAL - 1, START + window_time, 0, START)
+    }
+
+    #[test]
+    fn retarget_follows_the_window_time() {
+        let expected_time: u64 = (RETARGET_INTERVAL - 1) * BLOCK_SPEED;
+
+        assert_eq!(retarget_after(0xfff00000, expected_time), Some(0xfff00000));
+
+        // 4 times too fast gives 4 times less passing values, 2 times too slow gives twice as many
+        assert_eq!(retarget_after(0xfff00000, expected_time / 4), Some(0xff300000));
+        assert_eq!(retarget_after(0xfff00000, expected_time * 2), Some(0xfff10000));
+
+        // never more than MAX_RETARGET_FACTOR, even when the timestamps go backwards
+        assert_eq!(retarget_after(0xfff00000, 0), retarget_after(0xfff00000, expected_time / 4));
+        assert_eq!(retarget_after(0xfff00000, expected_time * 100), retarget_after(0xfff00000, expected_time * 4));
+        assert_eq!(get_retarget_difficulty(0xfff00000, RETARGET_INTERVAL - 1, START - 1, 0, START), Some(0xff300000));
+    }
+
+    #[test]
+    fn only_retarget_heights_change_the_difficulty() {
+        assert!(!is_retarget_height(0));
+        assert!(is_retarget_height(RETARGET_INTERVAL));
+        assert!(!is_retarget_height(RETARGET_INTERVAL + 1));
+
+        assert_eq!(get_retarget_difficulty(0xfff00000, RETARGET_INTERVAL, START + 1, 1, START), Some(0xfff00000));
+        assert_eq!(get_retarget_difficulty(0xfff00000, 0, START, 0, START), Some(0xfff00000));
+    }
+
+    #[test]
+    fn last_possible_height_has_no_next_difficulty() {
+        assert_eq!(get_retarget_difficulty(0xfff00000, u64::MAX, START, u64::MAX - RETARGET_INTERVAL, START), None);
+    }
+
+    /// count distinct reward transactions, told apart by their amount
+    fn transactions(count: usize) -> Vec<Transaction> {
+        let miner: Point = Secp256k1::new().g;
+        (1..=count).map(|amount| Transaction::reward_transaction_with(&miner, amount as f32, b"")).collect()
+    }
+
+    #[test]
+    fn merkle_proofs_lead_to_the_root() {
+        // 5 leaves are duplicated to 6, and the node left over on the second level is paired with the next level
+        let transactions: Vec<Transaction> = transactions(5);
+        let root: String = get_merkel_root(&transactions);
+
+        // first, middle and last
+        for index in [0, 2, 4] {
+            let proof: Vec<(String, bool)> = get_merkle_proof(&transactions, index);
+            assert!(verify_merkle_proof(&transactions[index].get_hash(), &proof, &root));
+
+            // the proof is only for that transaction
+            let other: usize = (index + 1) % transactions.len();
+            assert!(!verify_merkle_proof(&transactions[other].get_hash(), &proof, &root));
+        }
+
+        assert!(get_merkle_proof(&transactions, 5).is_empty());
+    }
+
+    #[test]
+    fn single_transaction_is_proven_by_its_duplicate() {
+        let transactions: Vec<Transaction> = transactions(1);
+        let root: String = get_merkel_root(&transactions);
+        let transaction_hash: String = transactions[0].get_hash();
+
+        let proof: Vec<(String, bool)> = get_merkle_proof(&transactions, 0);
+        assert_eq!(proof, vec![(transaction_hash.clone(), true)]);
+        assert!(verify_merkle_proof(&transaction_hash, &proof, &root));
+
+        // a transaction hash alone is never a root
+        assert!(!verify_merkle_proof(&transaction_hash, &[], &transaction_hash));
+    }
+
+    /// count distinct transaction ids, sorted
+    fn sorted_txids(count: usize) -> Vec<String> {
+        get_sorted_leaves(&(0..count).map(|i| hash(format!("transaction {}", i))).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn non_inclusion_proofs_show_the_missing_id() {
+        for count in 1..=5 {
+            let txids: Vec<String> = sorted_txids(count);
+            let root: String = get_sorted_merkle_root(&txids);
+
+            // before the first, between the first two, after the last
+            let mut targets: Vec<String> = vec!["".to_owned(), "g".to_owned()];
+            if count > 1 {
+                targets.push(txids[0].clone() + "0");
+            }
+
+            for target in targets {
+                let proof: NonInclusionProof = merkle_non_inclusion_proof(&txids, &target).unwrap();
+                assert!(verify_merkle_non_inclusion_proof(&target, &proof, &root), "{} of {}", target, count);
+
+                // the same proof doesn't show that an id of the tree is missing
+                for txid in &txids {
+                    assert!(!verify_merkle_non_inclusion_proof(txid, &proof, &root));
+                }
+            }
+
+            for txid in &txids {
+                assert!(merkle_non_inclusion_proof(&txids, txid).is_none());
+            }
+        }
+
+        let empty: NonInclusionProof = merkle_non_inclusion_proof(&[], "target").unwrap();
+        assert!(verify_merkle_non_inclusion_proof("target", &empty, &get_sorted_merkle_root(&[])));
+    }
+
+    #[test]
+    fn forged_non_inclusion_proofs_are_rejected() {
+        let txids: Vec<String> = sorted_txids(5);
+        let root: String = get_sorted_merkle_root(&txids);
+        let target: String = txids[2].clone() + "0";
+        let proof: NonInclusionProof = merkle_non_inclusion_proof(&txids, &target).unwrap();
+        assert!(verify_merkle_non_inclusion_proof(&target, &proof, &root));
+
+        // the siblings still reach the top in a tree of 6 or 8 leaves, but the leaf count is part of the root
+        for leaf_count in [4, 6, 8] {
+            let mut forged: NonInclusionProof = proof.clone();
+            forged.leaf_count = leaf_count;
+            assert!(!verify_merkle_non_inclusion_proof(&target, &forged, &root));
+        }
+
+        let mut forged: NonInclusionProof = proof.clone();
+        forged.right.as_mut().unwrap().siblings[0] = hash("sibling".to_owned());
+        assert!(!verify_merkle_non_inclusion_proof(&target, &forged, &root));
+
+        // ids that aren't next to each other leave a gap where the target could be
+        let mut forged: NonInclusionProof = proof.clone();
+        forged.right = merkle_non_inclusion_proof(&txids, &(txids[3].clone() + "0")).unwrap().right;
+        let target: String = txids[3].clone();
+        assert!(!verify_merkle_non_inclusion_proof(&target, &forged, &root));
+
+        // the level above the leaves passed off as the leaves of a smaller tree, with an id of the tree between two of its nodes
+        let (txids, level): (Vec<String>, Vec<String>) = (0..)
+            .map(|start: usize| {
+                let txids: Vec<String> = get_sorted_leaves(&(start..start + 4).map(|i| hash(format!("transaction {}", i))).collect::<Vec<String>>());
+                let level: Vec<String> = get_next_sorted_level(&txids.iter().map(|txid| hash_sorted_leaf(txid)).collect::<Vec<String>>());
+                (txids, level)
+            })
+            .find(|(txids, level)| txids.iter().any(|txid| level[0] < *txid && *txid < level[1]))
+            .unwrap();
+        let target: &String = txids.iter().find(|txid| level[0] < **txid && **txid < level[1]).unwrap();
+
+        let forged: NonInclusionProof = NonInclusionProof {
+            leaf_count: 2,
+            left: Some(SortedMerkleLeaf { index: 0, txid: level[0].clone(), siblings: vec![level[1].clone()] }),
+            right: Some(SortedMerkleLeaf { index: 1, txid: level[1].clone(), siblings: vec![level[0].clone()] })
+        };
+        assert!(!verify_merkle_non_inclusion_proof(target, &forged, &get_sorted_merkle_root(&txids)));
+    }
+
+    #[test]
+    fn base64_matches_the_standard_vectors() {
+        // the test vectors of rfc 4648
+        let vectors: [(&str, &str); 7] = [
+            ("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")
+        ];
+
+        for (bytes, text) in vectors {
+            assert_eq!(encode_base64(bytes.as_bytes()), text);
+            assert_eq!(decode_base64(text).unwrap(), bytes.as_bytes());
+        }
+
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&all)).unwrap(), all);
+
+        // bad length, padding before the end, too much padding and characters outside the alphabet
+        assert!(decode_base64("Zm9").is_none());
+        assert!(decode_base64("Zg==Zm9v").is_none());
+        assert!(decode_base64("Z===").is_none());
+        assert!(decode_base64("Zm9!").is_none());
+    }
+
+    #[test]
+    fn target_difficulty_estimates_close_to_the_target_time() {
+        // every hash passes the easiest difficulty, one in 16^8 passes the hardest
+        assert!((estimate_block_time(0xffffffff, 1.0) - 1.0).abs() < 1e-9);
+        assert!((estimate_block_time(0, 1.0) - (1u64 << 32) as f64).abs() < 1e-3);
+        assert!((estimate_block_time(0x0fffffff, 1000.0) - 0.016).abs() < 1e-9);
+
+        for hashrate in [10.0, 1e3, 1e5, 1e6] {
+            for target_seconds in [1, 60, 600, 1200] {
+                let difficulty: u32 = target_difficulty(hashrate, target_seconds);
+                let estimate: f64 = estimate_block_time(difficulty, hashrate);
+
+                let target: f64 = target_seconds as f64;
+                assert!(estimate >= target / 2.0 && estimate <= target * 2.0, "{} {} {:x} {}", hashrate, target_seconds, difficulty, estimate);
+            }
+        }
+
+        // more blocks than hashes a second is as easy as it gets, and a too slow target is as hard as it gets
+        assert_eq!(target_difficulty(1.0, 0), 0xffffffff);
+        assert_eq!(target_difficulty(1e9, 1_000_000), 0);
+    }
+
+    #[test]
+    fn orphan_rate_is_about_propagation_over_block_time() {
+        assert_eq!(expected_orphan_rate(BLOCK_SPEED, 0.0), 0.0);
+
+        // 12 seconds out of 1200 is about 1%, just under it
+        let rate: f64 = expected_orphan_rate(BLOCK_SPEED, 12.0);
+        assert!(rate < 0.01 && rate > 0.0099);
+
+        // propagating for as long as a block takes orphans most blocks but never all of them
+        assert!((expected_orphan_rate(BLOCK_SPEED, BLOCK_SPEED as f64) - (1.0 - (-1f64).exp())).abs() < 1e-12);
+        assert!(expected_orphan_rate(BLOCK_SPEED, 100.0 * BLOCK_SPEED as f64) <= 1.0);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,335 @@
+use core::fmt;
+use sha256::hash;
+use super::{functions, Block, Transaction};
+use serde::{Serialize, Deserialize};
+
+/// The header of a block in the blockchain, which is every field of the block except its transactions.
+/// Headers are enough to follow the chain's linkage and proof of work, so light clients only need these
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    /// The height of the block, how many blocks is it above genesis
+    pub(crate) height: u64,
+
+    /// The hash of the block
+    pub(crate) hash: String,
+
+    /// The timestamp of the block
+    pub(crate) timestamp: u64,
+
+    /// The hash of the previous block
+    pub(crate) prev_hash: String,
+
+    /// The nonce of the block, used for hashing to comply with difficulty
+    pub(crate) nonce: u32,
+
+    /// The difficulty rating of the block
+    pub(crate) difficulty: u32,
+
+    /// The merkel root of the block
+    pub(crate) merkel_root: String,
+
+    /// The number of transactions in the block, hashed so that it can't be changed without changing the hash
+    pub(crate) transaction_count: u64
+}
+
+/// adds display for BlockHeader struct for easy printing
+impl fmt::Display for BlockHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\theight: {}\n\thash: {}\n\ttimestamp: {}\n\tprev_hash: {}\n\tnonce: {}\n\tdifficulty: {}\n\tmerkel root: {}\n\ttransaction count: {}", 
+            self.height, 
+            self.hash,
+            self.timestamp,
+            self.prev_hash,
+            self.nonce,
+            self.difficulty,
+            self.merkel_root,
+            self.transaction_count)
+    }
+}
+
+impl BlockHeader {
+    /// returns the header's block hash
+    pub fn get_hash(&self) -> String {
+        self.hash.clone()
+    }
+
+    /// returns the header's merkel root
+    pub fn get_merkel_root(&self) -> String {
+        self.merkel_root.clone()
+    }
+
+    /// returns the header's previous hash
+    pub fn get_prev_hash(&self) -> String {
+        self.prev_hash.clone()
+    }
+
+    /// returns the header's nonce
+    pub fn get_nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    /// returns the header's difficulty
+    pub fn get_difficulty(&self) -> u32 {
+        self.difficulty
+    }
+
+    /// returns the header's timestamp
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// returns the header's height
+    pub fn get_height(&self) -> u64 {
+        self.height
+    }
+
+    /// returns the header's transaction count
+    pub fn get_transaction_count(&self) -> u64 {
+        self.transaction_count
+    }
+
+    /// gets the message that was used to hash the header's block
+    pub fn get_message(&self) -> String {
+        functions::get_block_message(self.height, 
+                                     self.timestamp, 
+                                     &self.prev_hash, 
+                                     self.nonce, 
+                                     self.difficulty, 
+                                     &self.merkel_root,
+                                     self.transaction_count)
+    }
+
+    /// verifies if the hash of the header fits with current data
+    /// 
+    /// # Returns
+    /// * True if the hash is correct, false otherwise
+    pub fn confirm_hash(&self) -> bool {
+        self.get_hash() == hash(self.get_message())
+    }
+
+    /// verifies on the header if the difficulty and hash match
+    pub fn confirm_difficulty(&self) -> bool {
+        Block::verify_difficulty(self.get_hash(), self.get_difficulty())
+    }
+
+    /// verifies that a transaction is in the header's block with a merkel proof, without needing the block's transactions.
+    /// the header's hash must also be correct, otherwise the merkel root it holds could be anything
+    /// 
+    /// # Arguments
+    /// * `tx` - A &Transaction which specifies the transaction to prove
+    /// * `proof` - The (sibling hash, is the sibling on the right) pairs made by get_merkle_proof for the transaction
+    /// 
+    /// # Returns
+    /// * True if the proof leads from the transaction to the header's merkel root, false otherwise
+    /// 
+    pub fn verify_transaction_inclusion(&self, tx: &Transaction, proof: &[(String, bool)]) -> bool {
+        self.confirm_hash() && functions::verify_merkle_proof(&tx.get_hash(), proof, &self.merkel_root)
+    }
+
+    /// returns the difficulty the header on top of prev_header should have.
+    /// the headers equivalent of Block::get_supposed_difficulty
+    /// 
+    /// # Arguments
+    /// * `prev_header` - A &BlockHeader which specifies the header of the block before
+    /// * `window_start_header` - A &BlockHeader which specifies the header of the first block of the retarget window
+    /// 
+    /// # Returns
+    /// An Option<u32> which is the supposed difficulty of the next header, or None if prev_header is at the last possible height.
+    /// 
+    pub fn get_supposed_difficulty(prev_header: &BlockHeader, window_start_header: &BlockHeader) -> Option<u32> {
+        functions::get_retarget_difficulty(prev_header.get_difficulty(), 
+                                           prev_header.get_height(), 
+                                           prev_header.get_timestamp(), 
+                                           window_start_header.get_height(), 
+                                           window_start_header.get_timestamp())
+    }
+
+    /// validates a sequence of headers without needing the blocks' transactions.
+    /// every header must have a correct hash that satisfies its difficulty, each header
+    /// must be directly on top of the one before it, and its difficulty must be the one
+    /// retargeting computes from the headers before it. a retarget whose window starts before
+    /// the first header can't be computed, so it's only checked when the headers start from far enough
+    /// 
+    /// # Arguments
+    /// * `headers` - A slice of headers ordered by height
+    /// 
+    /// # Returns
+    /// * True if the headers form a valid chain, false otherwise
+    /// 
+    pub fn validate_headers(headers: &[BlockHeader]) -> bool {
+        for (i, header) in headers.iter().enumerate() {
+            if !header.confirm_hash() || !header.confirm_difficulty() {
+                eprintln!("Header at height {} has an invalid proof of work", header.height);
+                return false;
+            }
+
+            // first header has nothing to link to
+            if i == 0 {
+                continue;
+            }
+
+            let prev_header: &BlockHeader = &headers[i - 1];
+
+            // the heights are untrusted, the last possible height can't have a header on top of it
+            let next_height: u64 = match prev_header.height.checked_add(1) {
+                Some(height) => height,
+                None => {
+                    eprintln!("Header at height {} can't have a header on top of it", prev_header.height);
+                    return false;
+                }
+            };
+
+            if header.height != next_height || header.prev_hash != prev_header.hash {
+                eprintln!("Header at height {} does not link to the previous header", header.height);
+                return false;
+            }
+
+            // heights are consecutive from here, so the window start is found by its height
+            let window_start_height: u64 = functions::get_retarget_window_start(header.height);
+            let supposed_difficulty: u32 = if window_start_height >= headers[0].height {
+                let window_start_header: &BlockHeader = &headers[(window_start_height - headers[0].height) as usize];
+
+                // checked above that there can be a header on top of prev_header
+                BlockHeader::get_supposed_difficulty(prev_header, window_start_header).unwrap()
+            } else if functions::is_retarget_height(header.height) {
+                // the window starts before the first header, the retarget can't be checked
+                continue;
+            } else {
+                prev_header.difficulty
+            };
+
+            // a header could claim an easier difficulty than it should have to be cheaper to mine
+            if header.difficulty != supposed_difficulty {
+                eprintln!("Header at height {} has difficulty {:08x} but should have {:08x}", header.height, header.difficulty, supposed_difficulty);
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the headers of genesis and of count blocks mined on top of it at their retargeted difficulty.
+    /// genesis is mined at a harder difficulty than GENESIS_DIFFICULTY so that a header can claim an easier one than it keeps
+    fn chain_headers(count: usize) -> Vec<BlockHeader> {
+        let mut genesis: Block = Block::new_genesis();
+        genesis.set_difficulty(0xffffff00);
+        while !genesis.confirm_difficulty() {
+            genesis.increment_and_hash();
+        }
+
+        let mut blocks: Vec<Block> = vec![genesis];
+
+        for _ in 0..count {
+            let prev_block: &Block = blocks.last().unwrap();
+            let window_start_block: &Block = &blocks[functions::get_retarget_window_start(prev_block.get_height() + 1) as usize];
+            let mut block: Block = Block::new(prev_block, window_start_block, &vec![]).unwrap();
+
+            while !block.confirm_difficulty() {
+                block.increment_and_hash();
+            }
+
+            blocks.push(block);
+        }
+
+        blocks.iter().map(|block| block.get_header()).collect()
+    }
+
+    /// sets the header's hash back to the hash of its fields after they were changed
+    fn rehash(header: &mut BlockHeader) {
+        header.hash = hash(header.get_message());
+    }
+
+    #[test]
+    fn linked_headers_are_valid() {
+        let headers: Vec<BlockHeader> = chain_headers(3);
+        assert!(BlockHeader::validate_headers(&headers));
+        assert!(BlockHeader::validate_headers(&headers[1..]));
+        assert!(BlockHeader::validate_headers(&[]));
+    }
+
+    #[test]
+    fn broken_links_are_invalid() {
+        let headers: Vec<BlockHeader> = chain_headers(3);
+
+        let mut wrong_prev_hash: Vec<BlockHeader> = headers.clone();
+        wrong_prev_hash[2].prev_hash = headers[0].hash.clone();
+        rehash(&mut wrong_prev_hash[2]);
+        assert!(!BlockHeader::validate_headers(&wrong_prev_hash));
+
+        let mut skipped_height: Vec<BlockHeader> = headers.clone();
+        skipped_height[2].height += 1;
+        rehash(&mut skipped_height[2]);
+        assert!(!BlockHeader::validate_headers(&skipped_height));
+
+        // a changed field without the hash following it
+        let mut wrong_hash: Vec<BlockHeader> = headers.clone();
+        wrong_hash[1].timestamp += 1;
+        assert!(!BlockHeader::validate_headers(&wrong_hash));
+    }
+
+    #[test]
+    fn header_on_top_of_the_last_height_is_invalid() {
+        let mut headers: Vec<BlockHeader> = chain_headers(1);
+
+        // at the easiest difficulty the rehashed headers don't need mining again
+        headers[0].height = u64::MAX;
+        headers[0].difficulty = 0xffffffff;
+        rehash(&mut headers[0]);
+        headers[1].height = 0;
+        headers[1].difficulty = 0xffffffff;
+        headers[1].prev_hash = headers[0].hash.clone();
+        rehash(&mut headers[1]);
+
+        assert!(BlockHeader::validate_headers(&headers[..1]));
+        assert!(!BlockHeader::validate_headers(&headers));
+    }
+
+    #[test]
+    fn header_claiming_an_easier_difficulty_is_invalid() {
+        let mut headers: Vec<BlockHeader> = chain_headers(2);
+        assert!(BlockHeader::validate_headers(&headers));
+
+        // every hash satisfies the easiest difficulty, so only retargeting can catch it
+        headers[2].difficulty = 0xffffffff;
+        rehash(&mut headers[2]);
+        assert!(headers[2].confirm_difficulty());
+        assert!(!BlockHeader::validate_headers(&headers));
+    }
+
+    #[test]
+    fn transaction_is_proven_in_a_block_from_its_header() {
+        let miner: ecdsa::secp256k1::Point = ecdsa::secp256k1::Secp256k1::new().g;
+        let transactions: Vec<Transaction> = (1..=3)
+            .map(|amount| Transaction::reward_transaction_with(&miner, amount as f32, b""))
+            .collect();
+
+        let genesis: Block = Block::new_genesis();
+        let block: Block = Block::new(&genesis, &genesis, &transactions).unwrap();
+        let header: BlockHeader = block.get_header();
+
+        for (index, transaction) in transactions.iter().enumerate() {
+            let proof: Vec<(String, bool)> = functions::get_merkle_proof(&transactions, index);
+            assert!(header.verify_transaction_inclusion(transaction, &proof));
+
+            // a sibling changed, or swapped to the other side
+            let mut tampered: Vec<(String, bool)> = proof.clone();
+            tampered[0].0 = hash("sibling".to_owned());
+            assert!(!header.verify_transaction_inclusion(transaction, &tampered));
+
+            let mut tampered: Vec<(String, bool)> = proof.clone();
+            tampered[1].1 = !tampered[1].1;
+            assert!(!header.verify_transaction_inclusion(transaction, &tampered));
+        }
+
+        // the merkel root of a header whose hash doesn't match can't be trusted
+        let mut changed: BlockHeader = header.clone();
+        changed.timestamp += 1;
+        assert!(!changed.verify_transaction_inclusion(&transactions[0], &functions::get_merkle_proof(&transactions, 0)));
+    }
+}
\ No newline at end of file
@@ -1,12 +1,27 @@
-const BLOCK_SPEED: u64 = 1200; // 20 min between blocks
-const TRANSACTION_LIMIT_PER_BLOCK: usize = 5000;
+pub const BLOCK_SPEED: u64 = 1200; // 20 min between blocks
+pub const TRANSACTION_LIMIT_PER_BLOCK: usize = 5000;
 const REWARD: f32 = 1.5;
+const MAX_TAG_BYTES: usize = 100; // most bytes a miner can tag its reward with
+pub const GENESIS_DIFFICULTY: u32 = 0xffffffff; // every hash satisfies it, genesis isn't mined
+pub const GENESIS_TIMESTAMP: u64 = 1712016000; // fixed so that every node makes the exact same genesis
+const RETARGET_INTERVAL: u64 = 72; // blocks between difficulty retargets, a day at BLOCK_SPEED
+const MAX_RETARGET_FACTOR: f64 = 4.0; // most a retarget can make the difficulty easier or harder
+pub const NETWORK_MAGIC: u32 = 0x736e6c6d; // "snlm", hashed in every block so other networks' blocks don't validate here
 
 mod block;
-pub use block::Block;
+pub use block::{Block, BlockError};
+
+mod header;
+pub use header::BlockHeader;
+
+mod clock;
+pub use clock::{Clock, SystemClock};
 
 mod functions;
 
+mod proof;
+pub use proof::{NonInclusionProof, SortedMerkleLeaf};
+
 mod transaction;
-pub use transaction::Transaction;
-pub use functions::get_merkel_root;
+pub use transaction::{Transaction, TransactionError};
+pub use functions::{get_merkel_root, get_total_fees, get_merkle_proof, verify_merkle_proof, get_sorted_merkle_root, merkle_non_inclusion_proof, verify_merkle_non_inclusion_proof, get_max_tps, get_daily_growth_bytes, expected_orphan_rate, estimate_block_time, target_difficulty, block_hash, get_retarget_window_start};
@@ -0,0 +1,58 @@
+use serde::{Serialize, Deserialize};
+
+/// A transaction id of a sorted merkel tree along with what's needed to prove it's in the tree
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SortedMerkleLeaf {
+    /// The position of the transaction id in the sorted transaction ids
+    pub(crate) index: usize,
+
+    /// The transaction id, the hash of the transaction
+    pub(crate) txid: String,
+
+    /// The sibling hashes from the transaction id up to the root, their side is given by the index
+    pub(crate) siblings: Vec<String>
+}
+
+impl SortedMerkleLeaf {
+    /// returns the position of the transaction id in the sorted transaction ids
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    /// returns the transaction id
+    pub fn get_txid(&self) -> String {
+        self.txid.clone()
+    }
+}
+
+/// The proof that a transaction id isn't in a sorted merkel tree.
+/// It's the two transaction ids right below and right above the missing one, which are next to each other in the tree,
+/// or only one of them when the missing id would be before the first or after the last transaction id
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NonInclusionProof {
+    /// The number of transaction ids in the tree, which fixes where the last id of every level is duplicated
+    pub(crate) leaf_count: usize,
+
+    /// The transaction id right below the missing one, None if the missing one would be first
+    pub(crate) left: Option<SortedMerkleLeaf>,
+
+    /// The transaction id right above the missing one, None if the missing one would be last
+    pub(crate) right: Option<SortedMerkleLeaf>
+}
+
+impl NonInclusionProof {
+    /// returns the number of transaction ids in the tree
+    pub fn get_leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// returns the transaction id right below the missing one
+    pub fn get_left(&self) -> Option<SortedMerkleLeaf> {
+        self.left.clone()
+    }
+
+    /// returns the transaction id right above the missing one
+    pub fn get_right(&self) -> Option<SortedMerkleLeaf> {
+        self.right.clone()
+    }
+}
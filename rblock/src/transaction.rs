@@ -1,8 +1,9 @@
 use core::fmt;
+use std::sync::OnceLock;
 use ecdsa::secp256k1::{sign, verify_signature, Point, Signature};
 use num_bigint::BigInt;
 use serde::{Deserialize, Serialize};
-use super::REWARD;
+use super::{MAX_TAG_BYTES, REWARD};
 
 /// A transaction in the blockchain
 #[derive(Clone, Deserialize, Serialize)]
@@ -16,21 +17,78 @@ pub struct Transaction {
     /// The amount of the transaction
     amount: f32,
 
+    /// The fee the sender pays on top of the amount, it goes to the miner of the block
+    fee: f32,
+
     /// The digital signature of the transaction, signed by the sender
-    signature: Signature
+    signature: Signature,
+
+    /// Bytes the miner tags its reward with, empty for every other transaction since it isn't signed
+    tag: Vec<u8>,
+
+    /// The address of the sender, derived the first time it's asked for and never serialized
+    #[serde(skip)]
+    sender_address: OnceLock<String>,
+
+    /// The hash of the transaction, computed the first time it's asked for and never serialized
+    #[serde(skip)]
+    hash: OnceLock<String>
 }
 
 /// implement display for transaction struct for easy printing
 impl fmt::Display for Transaction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "\tsender: {}\n\trecipient: {}\n\tamount: {}\n\tsignature: {}", 
+        write!(f, "\tsender: {}\n\trecipient: {}\n\tamount: {}\n\tfee: {}\n\tsignature: {}", 
             self.sender, 
             self.recipient,
             self.amount,
+            self.fee,
             self.signature)
     }
 }
 
+/// The reasons a transaction can be invalid on its own, without looking at balances
+#[derive(Debug, PartialEq)]
+pub enum TransactionError {
+    /// The amount is zero, negative or not a finite number
+    InvalidAmount(f32),
+
+    /// The fee is negative or not a finite number, or a reward has one
+    InvalidFee(f32),
+
+    /// The sender is not a point on the curve
+    InvalidSender,
+
+    /// The reward's recipient is not a point on the curve, so the reward could never be spent
+    InvalidRecipient,
+
+    /// The signature's values are out of range
+    SignatureOutOfRange,
+
+    /// The signature doesn't match the sender and the transaction
+    InvalidSignature,
+
+    /// The tag is longer than MAX_TAG_BYTES, or is on a transaction that isn't a reward
+    InvalidTag(usize)
+}
+
+/// implement display for transaction errors for easy printing
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::InvalidAmount(amount) => write!(f, "amount {} is not a positive number", amount),
+            TransactionError::InvalidFee(fee) => write!(f, "fee {} is not allowed", fee),
+            TransactionError::InvalidSender => write!(f, "sender is not on the curve"),
+            TransactionError::InvalidRecipient => write!(f, "recipient is not on the curve"),
+            TransactionError::SignatureOutOfRange => write!(f, "signature values are out of range"),
+            TransactionError::InvalidSignature => write!(f, "signature is invalid"),
+            TransactionError::InvalidTag(length) => write!(f, "tag of {} bytes is not allowed", length)
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
 impl Transaction { 
     /// generates a reward transaction for the miner that doesn't need to be signed
     /// 
@@ -41,15 +99,36 @@ impl Transaction {
     /// * a new transaction with the reward amount
     /// 
     pub fn reward_transaction(recipient: &Point) -> Self {
+        Transaction::reward_transaction_with(recipient, REWARD, &[])
+    }
+
+    /// generates a reward transaction for the miner with an explicit amount and a tag, 
+    /// for when the allowed reward isn't just the REWARD constant.
+    /// blocks only accept a reward with the allowed amount, the tag is hashed with the reward
+    /// so a miner can change it to change the block's merkel root
+    /// 
+    /// # Arguments
+    /// * `miner` - the public key of the miner
+    /// * `amount` - the amount of the reward
+    /// * `tag` - the bytes to tag the reward with, at most MAX_TAG_BYTES
+    /// 
+    /// # Returns
+    /// * a new transaction with the given reward amount and tag
+    /// 
+    pub fn reward_transaction_with(miner: &Point, amount: f32, tag: &[u8]) -> Self {
         Transaction {
             sender: Point::identity(),
-            recipient: recipient.clone(),
-            amount: REWARD,
-            signature: Signature::get_empty()
+            recipient: miner.clone(),
+            amount,
+            fee: 0.0,
+            signature: Signature::get_empty(),
+            tag: tag.to_vec(),
+            sender_address: OnceLock::new(),
+            hash: OnceLock::new()
         }
     }
 
-    /// returns a new transaction that has already been signed using the private key
+    /// returns a new transaction without a fee that has already been signed using the private key
     /// 
     /// # Arguments
     /// * `sender` - the public key of the sender
@@ -61,46 +140,345 @@ impl Transaction {
     /// * a new transaction with the sender, recipient, amount, and signature
     /// 
     pub fn new(sender: &Point, recipient: &Point, amount: f32, private_key: &BigInt) -> Self {
-        let message: String = sender.to_string() + &recipient.to_string() + &amount.to_string();
-        let signature: Signature = sign(&message, private_key.clone(), None);
+        Transaction::new_with_fee(sender, recipient, amount, 0.0, private_key)
+    }
 
-        Transaction {
+    /// returns a new transaction with a fee for the miner that has already been signed using the private key.
+    /// the fee is signed along with the rest so the fee can't be changed after
+    /// 
+    /// # Arguments
+    /// * `sender` - the public key of the sender
+    /// * `recipient` - the public key of the recipient
+    /// * `amount` - the amount of the transaction
+    /// * `fee` - the fee paid by the sender on top of the amount
+    /// * `private_key` - the private key of the sender, used to sign the transaction
+    /// 
+    /// # Returns
+    /// * a new transaction with the sender, recipient, amount, fee, and signature
+    /// 
+    pub fn new_with_fee(sender: &Point, recipient: &Point, amount: f32, fee: f32, private_key: &BigInt) -> Self {
+        let mut transaction: Transaction = Transaction {
             sender: sender.clone(),
             recipient: recipient.clone(),
             amount,
-            signature
-        }
+            fee,
+            signature: Signature::get_empty(),
+            tag: Vec::new(),
+            sender_address: OnceLock::new(),
+            hash: OnceLock::new()
+        };
+
+        // sign exactly what verify checks
+        transaction.signature = sign(&transaction.signing_hash(), private_key.clone(), None);
+
+        transaction
     }
 
     /// returns the sender's public key
     pub fn get_sender(&self) -> Point { self.sender.clone() }
 
+    /// returns the sender's address, which is only computed once per transaction
+    /// so that indexing by sender doesn't hash the sender every time
+    pub fn sender_address(&self) -> String {
+        self.sender_address.get_or_init(|| self.sender.to_address()).clone()
+    }
+
     /// returns the recipient's public key
     pub fn get_recipient(&self) -> Point { self.recipient.clone() }
 
     /// returns the amount of the transaction
     pub fn get_amount(&self) -> f32 { self.amount.clone() }
 
+    /// returns the fee of the transaction, paid by the sender to the miner
+    pub fn get_fee(&self) -> f32 { self.fee }
+
     /// returns the signature of the transaction
     pub fn get_signature(&self) -> Signature { self.signature.clone() }
 
+    /// returns the tag of the transaction, only a reward can have one
+    pub fn get_tag(&self) -> Vec<u8> { self.tag.clone() }
+
+    /// returns the signature of the transaction, None for miner rewards since they aren't signed
+    pub fn signature(&self) -> Option<&Signature> {
+        if self.sender == Point::identity() {
+            return None;
+        }
+
+        Some(&self.signature)
+    }
+
     /// verifies the signature of the transaction
     /// 
     /// # Returns
     /// * true if the signature is valid, false otherwise
     /// 
     pub fn verify(&self) -> bool {
-        verify_signature(&self.signature, &self.get_message(), self.sender.clone())
+        self.verify_with_pubkey(&self.sender)
+    }
+
+    /// verifies the signature of the transaction against a given public key instead of the sender
+    /// 
+    /// # Arguments
+    /// * `pubkey` - A &Point which specifies the public key that should have signed the transaction
+    /// 
+    /// # Returns
+    /// * true if the signature is valid for the public key, false otherwise
+    /// 
+    pub fn verify_with_pubkey(&self, pubkey: &Point) -> bool {
+        verify_signature(&self.signature, &self.signing_hash(), pubkey.clone())
+    }
+
+    /// validates the transaction on its own, checking everything except if the sender can afford it.
+    /// miner rewards are only checked for their amount, fee, recipient and tag since they aren't signed
+    /// 
+    /// # Returns
+    /// * Ok if the transaction is valid, or the first reason it is invalid
+    /// 
+    pub fn validate(&self) -> Result<(), TransactionError> {
+        // also catches NaN
+        if !(self.amount.is_finite() && self.amount > 0.0) {
+            return Err(TransactionError::InvalidAmount(self.amount));
+        }
+
+        if !(self.fee.is_finite() && self.fee >= 0.0) {
+            return Err(TransactionError::InvalidFee(self.fee));
+        }
+
+        // Point::identity is miner reward sender, the fees are already in its amount
+        if self.sender == Point::identity() {
+            if self.fee != 0.0 {
+                return Err(TransactionError::InvalidFee(self.fee));
+            }
+
+            // also rejects the identity point, which isn't on the curve
+            if !self.recipient.is_on_curve() {
+                return Err(TransactionError::InvalidRecipient);
+            }
+
+            if self.tag.len() > MAX_TAG_BYTES {
+                return Err(TransactionError::InvalidTag(self.tag.len()));
+            }
+
+            return Ok(());
+        }
+
+        if !self.sender.is_on_curve() {
+            return Err(TransactionError::InvalidSender);
+        }
+
+        // the tag isn't signed, anyone could change it on a signed transaction
+        if !self.tag.is_empty() {
+            return Err(TransactionError::InvalidTag(self.tag.len()));
+        }
+
+        if !self.signature.is_in_range() {
+            return Err(TransactionError::SignatureOutOfRange);
+        }
+
+        if !self.verify() {
+            return Err(TransactionError::InvalidSignature);
+        }
+
+        Ok(())
+    }
+
+    /// returns the hash that is signed by the sender, the hash of every field of the transaction except the signature
+    pub fn signing_hash(&self) -> String {
+        sha256::hash(self.get_message())
     }
 
-    /// returns the message that was signed
+    /// returns the canonical serialization of every field of the transaction except the signature.
+    /// fields are serialized as a whole so no field can be changed without changing the message
     fn get_message(&self) -> String {
-        self.sender.to_string() + &self.recipient.to_string() + &self.amount.to_string()
+        // unwrap because points and floats always serialize
+        serde_json::to_string(&(&self.sender, &self.recipient, self.amount, self.fee)).unwrap()
     }
 
-    /// returns the hash for the transaction, used in the block's merkel root exclusively
+    /// returns the hash for the transaction, its id in the block's merkel root and in lookups.
+    /// it's only computed once per transaction since the fields never change
     pub fn get_hash(&self) -> String {
-        sha256::hash(format!("{}{}{}{}", self.sender, self.recipient, self.amount, self.signature))
+        self.hash.get_or_init(|| {
+            // an empty tag adds nothing, so untagged transactions keep the same hash
+            let tag: String = self.tag.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+            sha256::hash(format!("{}{}{}{}{}{}", self.sender, self.recipient, self.amount, self.fee, self.signature, tag))
+        }).clone()
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecdsa::secp256k1::{get_curve_precomputed_points, Secp256k1, W};
+    use crate::Block;
+
+    fn key(n: u32) -> (Point, BigInt) {
+        let private_key: BigInt = BigInt::from(n) * BigInt::from(1234567u64) + BigInt::from(99);
+        (Secp256k1::new().g.multiply(private_key.clone(), W, get_curve_precomputed_points()).unwrap(), private_key)
+    }
+
+    #[test]
+    fn changing_any_signed_field_breaks_the_signature() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let (c, _) = key(3);
+        let transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        assert!(transaction.verify());
+
+        let original: serde_json::Value = serde_json::to_value(&transaction).unwrap();
+        let changes: Vec<(&str, serde_json::Value)> = vec![
+            ("sender", serde_json::to_value(&c).unwrap()),
+            ("recipient", serde_json::to_value(&c).unwrap()),
+            ("amount", serde_json::json!(2.0)),
+        ];
+
+        for (field, value) in changes {
+            let mut changed: serde_json::Value = original.clone();
+            assert_ne!(changed[field], value);
+            changed[field] = value;
+
+            let changed: Transaction = serde_json::from_value(changed).unwrap();
+            assert!(!changed.verify(), "changing the {} kept the signature valid", field);
+        }
+    }
+
+    #[test]
+    fn custom_reward_is_the_coinbase() {
+        let (miner, _) = key(1);
+        let reward: Transaction = Transaction::reward_transaction_with(&miner, 4.0, b"pool");
+
+        let genesis: Block = Block::new_genesis();
+        let block: Block = Block::new(&genesis, &genesis, &vec![reward.clone()]).unwrap();
+
+        let coinbase: Transaction = block.get_reward_transaction().unwrap();
+        assert_eq!(coinbase.get_hash(), reward.get_hash());
+        assert_eq!(coinbase.get_amount(), 4.0);
+        assert_eq!(coinbase.get_tag(), b"pool".to_vec());
+
+        // blocks only take the allowed reward
+        assert!(!block.confirm_transactions());
+        let allowed: Block = Block::new(&genesis, &genesis, &vec![Transaction::reward_transaction_with(&miner, REWARD, b"pool")]).unwrap();
+        assert!(allowed.confirm_transactions());
+    }
+
+    #[test]
+    fn tag_changes_the_hash() {
+        let (miner, _) = key(1);
+        let untagged: Transaction = Transaction::reward_transaction(&miner);
+
+        assert_eq!(untagged.get_hash(), Transaction::reward_transaction_with(&miner, REWARD, &[]).get_hash());
+        assert_ne!(untagged.get_hash(), Transaction::reward_transaction_with(&miner, REWARD, &[0]).get_hash());
+    }
+
+    #[test]
+    fn tag_is_only_allowed_on_rewards_up_to_the_limit() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        let long_tag: Vec<u8> = vec![0; MAX_TAG_BYTES + 1];
+        assert_eq!(Transaction::reward_transaction_with(&a, REWARD, &long_tag).validate(), Err(TransactionError::InvalidTag(MAX_TAG_BYTES + 1)));
+        assert!(Transaction::reward_transaction_with(&a, REWARD, &long_tag[1..]).validate().is_ok());
+
+        let mut transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        assert!(transaction.validate().is_ok());
+
+        transaction.tag = vec![1];
+        assert_eq!(transaction.validate(), Err(TransactionError::InvalidTag(1)));
+    }
+
+    #[test]
+    fn sender_address_is_cached_and_matches_the_point() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+
+        let address: String = transaction.sender_address();
+        assert_eq!(address, a.to_address());
+        assert_eq!(transaction.sender_address(), address);
+        assert_ne!(address, b.to_address());
+
+        // the cache isn't serialized, a deserialized transaction derives the same address again
+        let copy: Transaction = serde_json::from_str(&serde_json::to_string(&transaction).unwrap()).unwrap();
+        assert_eq!(copy.sender_address(), address);
+    }
+
+    #[test]
+    fn bad_signature_is_invalid() {
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+
+        let mut transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        assert!(transaction.validate().is_ok());
+
+        // signed by someone other than the sender
+        transaction.signature = Transaction::new(&a, &b, 1.0, &private_b).get_signature();
+        assert_eq!(transaction.validate(), Err(TransactionError::InvalidSignature));
+    }
+
+    #[test]
+    fn non_positive_amounts_are_invalid() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        for amount in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let transaction: Transaction = Transaction::new(&a, &b, amount, &private_a);
+            assert!(matches!(transaction.validate(), Err(TransactionError::InvalidAmount(_))));
+        }
+
+        assert!(matches!(Transaction::reward_transaction_with(&a, 0.0, &[]).validate(), Err(TransactionError::InvalidAmount(_))));
+    }
+
+    #[test]
+    fn sender_off_the_curve_is_invalid() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        let mut transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        transaction.sender.y += 1;
+        assert!(!transaction.sender.is_on_curve());
+        assert_eq!(transaction.validate(), Err(TransactionError::InvalidSender));
+    }
+
+    #[test]
+    fn reward_has_to_go_to_a_point_on_the_curve() {
+        let (miner, _) = key(1);
+        assert!(Transaction::reward_transaction(&miner).validate().is_ok());
+
+        let mut off_curve: Point = miner.clone();
+        off_curve.y += 1;
+
+        for address in [Point::identity(), off_curve] {
+            assert_eq!(Transaction::reward_transaction(&address).validate(), Err(TransactionError::InvalidRecipient));
+
+            // the block isn't given a reward that could never be spent
+            let genesis: Block = Block::new_genesis();
+            let mut block: Block = Block::new(&genesis, &genesis, &vec![]).unwrap();
+            block.reward_miner(&address);
+            assert!(block.get_reward_transaction().is_none());
+        }
+    }
+
+    #[test]
+    fn signature_is_verified_against_a_given_key() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+
+        assert!(transaction.verify());
+        assert!(transaction.verify_with_pubkey(&a));
+        assert!(!transaction.verify_with_pubkey(&b));
+    }
+
+    #[test]
+    fn reward_has_no_signature() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        let transaction: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        let signature: &Signature = transaction.signature().unwrap();
+        assert_eq!(serde_json::to_string(signature).unwrap(), serde_json::to_string(&transaction.get_signature()).unwrap());
+
+        assert!(Transaction::reward_transaction(&a).signature().is_none());
+    }
+}
\ No newline at end of file
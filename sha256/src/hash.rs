@@ -10,7 +10,39 @@ use crate::{HX, ROUND_CONSTANTS};
 /// * A string that holds the hashed data as a hexadecimal string
 /// 
 pub fn hash(data: String) -> String {
-    let bit_vec = get_processed_data(data);
+    // each char is taken as a single byte, like it always has been
+    let bit_vec = get_processed_data(data.chars().map(|c: char| c as u8), data.len());
+    compress(&bit_vec)
+}
+
+/// Function to hash bytes using the SHA-256 algorithm.
+/// Gives the same hash as `hash` for a string made of the same ascii bytes
+/// 
+/// # Arguments
+/// * `data` - A byte slice that holds the data to be hashed
+/// 
+/// # Returns
+/// * A string that holds the hashed data as a hexadecimal string
+/// 
+pub fn hash_bytes(data: &[u8]) -> String {
+    let bit_vec = get_processed_data(data.iter().copied(), data.len());
+    compress(&bit_vec)
+}
+
+
+// ------------------- Helper functions ------------------- //
+
+
+
+/// Function to compress processed data into the hexadecimal SHA-256 hash
+/// 
+/// # Arguments
+/// * `bit_vec` - A BitSlice that holds the processed data, a multiple of 512 bits long
+/// 
+/// # Returns
+/// * A string that holds the hashed data as a hexadecimal string
+/// 
+fn compress(bit_vec: &BitSlice) -> String {
     let closest_512_multiple: usize = ((bit_vec.len() + 512 - 1) / 512) * 512;
 
     let k: [u32; 64] = ROUND_CONSTANTS;
@@ -74,11 +106,6 @@ pub fn hash(data: String) -> String {
     return hash_values.iter().map(|&val| format!("{:08x}", val)).collect();
 }
 
-
-// ------------------- Helper functions ------------------- //
-
-
-
 /// Function to get the initial hash values for the SHA-256 algorithm.
 /// Values are hardcoded now, but they come from this function.
 /// 
@@ -199,21 +226,20 @@ fn right_rotate(x: u32, n: u32) -> u32 {
 /// 3. Add the number of bits from the original data in big-endian
 /// 
 /// # Arguments
-/// * `data` - A string that holds the data to be processed
+/// * `data` - An iterator over the bytes of the data to be processed
+/// * `data_len` - A usize which is the length of the original data in bytes
 /// 
 /// # Returns
 /// * A BitVec that holds the processed data
 ///
-fn get_processed_data(data: String) -> BitVec {
+fn get_processed_data(data: impl Iterator<Item = u8>, data_len: usize) -> BitVec {
     let mut bit_vec: BitVec = bitvec![];
 
     // iterate over every bit of the data and add it to the bitvec
-    for c in data.chars() {
-        let mut c_as_32: u32 = c as u32;
-
-        for _ in 0..8 { // 8 bits for 0-led chars
-            bit_vec.push((1 << 7) & c_as_32 != 0);
-            c_as_32 <<= 1;
+    for mut byte in data {
+        for _ in 0..8 {
+            bit_vec.push((1 << 7) & byte != 0);
+            byte <<= 1;
         }
     }
 
@@ -226,7 +252,7 @@ fn get_processed_data(data: String) -> BitVec {
         bit_vec.push(false);
     }
 
-    let mut data_n_bits: u64 = (data.len() * 8) as u64;
+    let mut data_n_bits: u64 = (data_len * 8) as u64;
     // add number of bits from original data in big-endian
     for _ in 0..64 {
         bit_vec.push(data_n_bits & (1 << 63) != 0);
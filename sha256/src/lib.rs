@@ -17,4 +17,4 @@ const ROUND_CONSTANTS: [u32; 64]= [1116352408, 1899447441, 3049323471, 392100957
 // pub hash module because I want initial hash values and round constants methods available               
 pub mod hash;
 
-pub use hash::hash;
\ No newline at end of file
+pub use hash::{hash, hash_bytes};
\ No newline at end of file
@@ -1,14 +1,35 @@
-use std::{collections::HashMap, io::{self, ErrorKind}, path::PathBuf};
+use std::{collections::{HashMap, HashSet}, io::{self, BufRead, ErrorKind, Read, Write}, ops::Range, path::{Path, PathBuf}};
 use dirs::home_dir;
 use ecdsa::secp256k1::Point;
-use rblock::{Block, Transaction};
-use rusty_leveldb::{DBIterator, LdbIterator, Options, Status, DB};
-use byteorder::{LittleEndian, ReadBytesExt};
+use rblock::{get_retarget_window_start, Block, BlockHeader, Clock, Transaction, BLOCK_SPEED, GENESIS_DIFFICULTY};
+use rusty_leveldb::{DBIterator, LdbIterator, Options, Status, WriteBatch, DB};
+use sha256::hash;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bincode::Options as _;
 use std::io::Cursor;
 
+use crate::handshake::HandshakeInfo;
+use crate::validator::ChainValidator;
+
 pub const DB_FILENAME: &'static str = ".r_blocks";
 const LATEST_BLOCK_KEY: &'static [u8; 6] = b"latest";
 const PUBLIC_KEY_PREFIX: &'static [u8; 7] = b"userPK_";
+#[allow(dead_code)]
+const GENESIS_ALLOCATIONS_KEY: &[u8; 11] = b"allocations";
+#[allow(dead_code)]
+const FORK_PREFIX: &[u8; 5] = b"fork_";
+#[allow(dead_code)]
+const MAX_OBSERVED_FORKS: usize = 100; // the lowest forks are dropped past this, so peers can't grow the db without bound
+#[allow(dead_code)]
+const MAX_HEADER_BYTES: u64 = 1024; // headers are a few hundred bytes, anything bigger is corrupt
+#[allow(dead_code)]
+const MAX_FRAME_BYTES: u64 = 16 * 1024 * 1024; // a full block is a few MB, a bigger frame is corrupt or an attack
+#[allow(dead_code)]
+pub const MEDIAN_TIME_SPAN: u64 = 11; // number of blocks whose timestamps make the median time past
+#[allow(dead_code)]
+pub const COINBASE_MATURITY: u64 = 100; // confirmations before a miner reward counts as spendable
+#[allow(dead_code)]
+const STALE_TIP_BLOCKS: u64 = 3; // block speeds without a new block before the chain is considered stalled
 
 
 /// A struct that represents a database of blocks.
@@ -27,15 +48,24 @@ impl BlocksDB {
     /// This method creates a new database file in the home directory of the user.
     /// 
     pub fn start_db() -> Result<Self, Status> {
-        let mut options: Options = Options::default();
-        options.create_if_missing = false; // create DB if missing
-        
         // get home directory
         let path: PathBuf = home_dir().ok_or_else(|| {
             io::Error::new(ErrorKind::NotFound, "Home directory could not be found")
         })?;
 
-        let db: DB = DB::open(path.join(DB_FILENAME), options)?;
+        BlocksDB::open(&path.join(DB_FILENAME))
+    }
+
+    /// Opens the database at a given path and returns a BlocksDB object with the database
+    /// 
+    /// # Arguments
+    /// * `path` - A &Path which specifies the directory of the database
+    /// 
+    pub fn open(path: &Path) -> Result<Self, Status> {
+        let mut options: Options = Options::default();
+        options.create_if_missing = false; // create DB if missing
+
+        let db: DB = DB::open(path, options)?;
         Ok(BlocksDB { db })
     }
 
@@ -49,6 +79,28 @@ impl BlocksDB {
 
         self.update_balance(point1, 10.).unwrap();
         self.update_balance(point2, 10.).unwrap();
+
+        // the balances under genesis aren't in any block, so they're kept to replay the chain from
+        let allocations: Vec<(Point, f32)> = vec![(point1.clone(), 10.), (point2.clone(), 10.)];
+        self.db.put(GENESIS_ALLOCATIONS_KEY, &bincode::serialize(&allocations).unwrap()).unwrap();
+        self.db.flush().unwrap();
+    }
+
+    /// Obtains the balances the chainstate started with under the genesis block, before any block was added.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Vec<(Point, f32)>, Status> which is the public keys and their starting balances, or an error if they are missing or corrupt.
+    /// 
+    pub fn genesis_allocations(&mut self) -> Result<Vec<(Point, f32)>, Status> {
+        match self.db.get(GENESIS_ALLOCATIONS_KEY) {
+            Some(bytes) => bincode::deserialize(&bytes).map_err(|e| 
+                Status::new(rusty_leveldb::StatusCode::Corruption, &format!("{e}"))
+            ),
+            None => Err(Status::new(rusty_leveldb::StatusCode::NotFound, "Genesis allocations not found"))
+        }
     }
 
     /// Reads and returns the block with a specific height if it exists
@@ -78,6 +130,31 @@ impl BlocksDB {
         }
     }
 
+    /// Reads and returns the blocks with heights from start to end, end excluded.
+    /// The range is clamped to the blocks in the db, so asking past the latest block returns the blocks up to it.
+    /// 
+    /// # Arguments
+    /// * `start` - A u64 that specifies the height of the first block
+    /// * `end` - A u64 that specifies the height after the last block
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Vec<Block>, Status> which is the blocks in the range, empty if none of them are in the db.
+    /// 
+    pub fn get_block_range(&mut self, start: u64, end: u64) -> Result<Vec<Block>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+        let end: u64 = end.min(latest_block_height + 1);
+
+        let mut blocks: Vec<Block> = Vec::new();
+        for height in start..end {
+            blocks.push(self.get_block(height)?);
+        }
+
+        Ok(blocks)
+    }
+
     /// Obtain latest block from the on-machine node database
     /// 
     /// # Modifications
@@ -156,7 +233,8 @@ impl BlocksDB {
 
 
     /// Adds a block into the db if it doesn't already exist.
-    /// Method should only be used to add a new highest block. It doesn't allow blocks other than the next one over to be added
+    /// Method should only be used to add a new highest block. It doesn't allow blocks other than the next one over to be added.
+    /// A valid block competing with one of the chain's blocks is still refused, but it's recorded as an observed fork
     /// 
     /// # Arguments
     /// * `block` - A &Block which specifies a reference to the block to put into the db
@@ -174,17 +252,32 @@ impl BlocksDB {
             return Err(Status::new(rusty_leveldb::StatusCode::NotSupported, &format!("Cannot add another genesis block")));
         }
 
+        // a block passing as genesis could be mistaken for it by anything looking blocks up by hash
+        if block.get_hash() == self.get_block(0)?.get_hash() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, &format!("Block at height {} claims the genesis hash", added_block_height)));
+        }
+
         // if the latest block is smaller than added block
         if latest_block_height == added_block_height - 1 {
+            // the height alone isn't enough, the block has to point to the latest block and not some other known block
+            BlocksDB::verify_link(&latest_block, block)?;
+
+            let window_start_block: Block = self.get_block(get_retarget_window_start(added_block_height))?;
+            BlocksDB::verify_block(&latest_block, &window_start_block, block)?;
+
+            // chainstate goes first since it's where the transactions get verified,
+            // a block with bad transactions must not become the latest block
+            self.update_chainstate(block.get_transactions())?;
 
             // update db with new latest block info
             self.update_latest_block(block)?;
-            self.update_chainstate(block.get_transactions())?;
 
         // if latest block is much smaller than added block
         } else if latest_block_height < added_block_height - 1 {
             return Err(Status::new(rusty_leveldb::StatusCode::NotSupported, &format!("Block height is greater next latest block.")));
         } else if latest_block_height >= added_block_height {
+            self.record_fork(block)?;
+
             return Err(Status::new(rusty_leveldb::StatusCode::NotSupported, &format!("Block height is much smaller than latest block's")));
         }
         
@@ -193,216 +286,3146 @@ impl BlocksDB {
         Ok(())
     }
 
-    /// Reads and returns the balance of a given adress.
+    /// Records a block as an observed fork if it's a valid block on top of one of the chain's blocks
+    /// that competes with the chain's block at the same height. Anything else isn't recorded, so peers can't fill the db with junk.
+    /// At most MAX_OBSERVED_FORKS are kept, the ones at the lowest heights are dropped first.
     /// 
     /// # Arguments
-    /// * `public_key` - A &Point which specifies a reference to the public key to lookup
+    /// * `block` - A &Block which specifies a reference to the block that wasn't adopted
     /// 
     /// # Modifications
-    /// This method changes the internal state of the DB object by calling get on it.
+    /// This method changes the internal state of the DB object by calling put on it.
     /// 
     /// # Returns
-    /// An Option<f32> which is the balance of the address if it exists in the db, or None if it does not.
+    /// An Result<(), Status> which is Ok(()) if the block was recorded or didn't need to be, or an error if the db could not be read or written.
     /// 
-    pub fn get_balance(&mut self, public_key: &Point) -> Result<f32, Status> {
+    fn record_fork(&mut self, block: &Block) -> Result<(), Status> {
+        let height: u64 = block.get_height();
 
-        // serialize the public_key to get the database key. Unwrap because Point never fails to serialize
-        match self.db.get(&BlocksDB::get_db_user_key(public_key)) {
-            Some(bytes) => {
+        // the same block sent again isn't a fork
+        if self.get_block(height)?.get_hash() == block.get_hash() {
+            return Ok(());
+        }
 
-                // wrap bytes buffer with a cursor for easy little-endian conversion to f32
-                let mut reader: Cursor<Vec<u8>> = Cursor::new(bytes);
+        let prev_block: Block = self.get_block(height - 1)?;
+        let window_start_block: Block = self.get_block(get_retarget_window_start(height))?;
 
-                Ok(reader.read_f32::<LittleEndian>()?) // Error if data is corrupted
-            },
-            None => {
-                // address was not found
-                Err(Status::new(rusty_leveldb::StatusCode::NotFound, &format!("Public key was not found.")))
-            } 
+        let verified: Result<(), Status> = BlocksDB::verify_link(&prev_block, block)
+            .and_then(|_| BlocksDB::verify_block(&prev_block, &window_start_block, block));
+
+        if verified.is_err() {
+            return Ok(());
+        }
+
+        self.db.put(&BlocksDB::fork_key(height, &block.get_hash()), &[])?;
+
+        // only the highest forks are kept, the lowest ones are the least likely to matter
+        let forks: Vec<(u64, String)> = self.observed_forks()?;
+        for (height, hash) in forks.iter().take(forks.len().saturating_sub(MAX_OBSERVED_FORKS)) {
+            self.db.delete(&BlocksDB::fork_key(*height, hash))?;
         }
+
+        self.db.flush()?;
+
+        Ok(())
     }
 
-    /// Method used to update balance for an existing user or create a new user with a specified balance.
-    /// 
-    /// # Arguments
-    /// * `public_key` - A &Point which specifies a reference to the public key to update the balance of
-    /// * `value` - A f32 which specifies the value to update the balance to
+    /// returns the key an observed fork is stored under, the prefix followed by the height and the hash of the block
+    fn fork_key(height: u64, hash: &str) -> Vec<u8> {
+        let mut key: Vec<u8> = FORK_PREFIX.to_vec();
+        key.extend_from_slice(&height.to_le_bytes());
+        key.extend_from_slice(hash.as_bytes());
+
+        key
+    }
+
+    /// Obtains every fork the node has seen but not adopted, as the height and hash of the competing block.
     /// 
     /// # Modifications
-    /// This method changes the internal state of the DB object by calling put on it.
+    /// This method changes the internal state of the DB object by iterating over it.
     /// 
     /// # Returns
-    /// An Result<(), Status> which is Ok(()) if the balance was successfully updated, or an error if it was not.
+    /// An Result<Vec<(u64, String)>, Status> which is the forks ordered by height, or an error if one of them is corrupt.
     /// 
-    fn update_balance(&mut self, public_key: &Point, value: f32) -> Result<(), Status> {
-        // account balances are stored in little-endian
-        self.db.put(&BlocksDB::get_db_user_key(public_key), &value.to_le_bytes())?;
-        self.db.flush()?;
-        Ok(())
+    pub fn observed_forks(&mut self) -> Result<Vec<(u64, String)>, Status> {
+        let mut forks: Vec<(u64, String)> = Vec::new();
+
+        let mut iter: DBIterator = self.db.new_iter()?;
+
+        let mut key: Vec<u8> = vec![];
+        let mut val: Vec<u8> = vec![];
+
+        while iter.advance() {
+            iter.current(&mut key, &mut val);
+
+            // fork keys are the prefix followed by the height and the hash of the block
+            if key.len() >= 13 && key[0..5] == *FORK_PREFIX {
+                let mut height_bytes: [u8; 8] = [0; 8];
+                height_bytes.copy_from_slice(&key[5..13]);
+
+                let hash: String = String::from_utf8(key[13..].to_vec()).map_err(|e| 
+                    Status::new(rusty_leveldb::StatusCode::Corruption, &format!("{e}"))
+                )?;
+
+                forks.push((u64::from_le_bytes(height_bytes), hash));
+            }
+        }
+
+        // keys are ordered by their bytes, which isn't the order of little-endian heights
+        forks.sort();
+
+        Ok(forks)
     }
 
+    /// Obtains the difficulty the next block on top of the latest block has to have.
+    /// It's the latest block's difficulty, except every RETARGET_INTERVAL blocks where it's retargeted
+    /// from how long the last window of blocks took.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<u32, Status> which is the difficulty of the next block, or an error if the blocks could not be read.
+    /// 
+    pub fn calculate_next_difficulty(&mut self) -> Result<u32, Status> {
+        let latest_block: Block = self.get_latest_block()?;
+        let window_start_block: Block = self.get_block(get_retarget_window_start(latest_block.get_height().saturating_add(1)))?;
+
+        Block::get_supposed_difficulty(&latest_block, &window_start_block).ok_or(
+            Status::new(rusty_leveldb::StatusCode::InvalidData, "No block can come after the latest block")
+        )
+    }
 
-    /// Method to add prefix to the user public keys to get the key in the db
+    /// Checks if a hash satisfies the difficulty the next block on top of the latest block has to have.
     /// 
     /// # Arguments
-    /// * `public_key` - A &Point which specifies a reference to the public key to get the db key for
+    /// * `hash` - A &str which specifies the hash to check
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
     /// 
     /// # Returns
-    /// A Vec<u8> which is the key in the db for the public key
+    /// An Result<bool, Status> which is true if the hash satisfies the next difficulty, or an error if the blocks could not be read.
     /// 
-    fn get_db_user_key(public_key: &Point) -> Vec<u8> {
-        // add prefix
-        let mut key: Vec<u8> = Vec::new();
-        key.extend_from_slice(PUBLIC_KEY_PREFIX);
-        key.extend_from_slice(&bincode::serialize(public_key).unwrap());
+    pub fn hash_meets_target(&mut self, hash: &str) -> Result<bool, Status> {
+        let difficulty: u32 = self.calculate_next_difficulty()?;
 
-        key
+        Ok(Block::verify_difficulty(hash.to_owned(), difficulty))
     }
 
-    
-    /// verifies that the transactions are valid and can be added to the chainstate.
-    /// makes a hashmap of all the new balances so that the new balances can be updated easily
+    /// Creates the block to mine on top of the latest block, with the difficulty it has to have.
     /// 
     /// # Arguments
-    /// * `transactions` - A Vec<Transaction> which specifies the transactions to verify
+    /// * `transactions` - A &Vec<Transaction> which specifies the transactions of the block
     /// 
     /// # Modifications
     /// This method changes the internal state of the DB object by calling get on it.
     /// 
     /// # Returns
-    /// An Result<HashMap<&Point, f32>, Status> which is returns a map of the new balances of the verified transactions
+    /// An Result<Block, Status> which is the block that still has to be mined, or an error if the blocks could not be read.
     /// 
-    fn verify_transactions(&mut self, transactions: &Vec<Transaction>) -> Result<HashMap<Point, f32>, Status> {
-        // hashmap to remember good balances
-        let mut balances: HashMap<Point, f32> = HashMap::new();
+    pub fn new_block(&mut self, transactions: &Vec<Transaction>) -> Result<Block, Status> {
+        let latest_block: Block = self.get_latest_block()?;
+        let window_start_block: Block = self.get_block(get_retarget_window_start(latest_block.get_height().saturating_add(1)))?;
 
-        for transaction in transactions {
-            let sender: Point = transaction.get_sender();
-            let recipient: Point = transaction.get_recipient();
+        let block: Block = Block::new(&latest_block, &window_start_block, transactions).map_err(|e| 
+            Status::new(rusty_leveldb::StatusCode::InvalidData, &format!("{e}"))
+        )?;
 
-            // Point::identity is miner reward
-            if sender != Point::identity() {
+        Ok(block)
+    }
 
-                // get original balances
-                // check hashmap first for balances
-                let sender_balance: f32 = *balances.get(&sender).unwrap_or(
-                    &self.get_balance(&sender).unwrap_or(0.0)
-                );
+    /// Adds a block made with new_block once an external miner found its nonce and the tag of its reward.
+    /// The block goes through every check add_block makes, and nothing in the db changes if it fails one.
+    /// 
+    /// # Arguments
+    /// * `template` - A Block which specifies the block that was given to the miner
+    /// * `nonce` - A u32 which specifies the nonce the miner found
+    /// * `tag` - A &[u8] which specifies the tag the miner put on the reward along with the nonce
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling put on it.
+    /// 
+    /// # Returns
+    /// An Result<Block, Status> which is the block that was added, or an error if the solution is invalid.
+    /// 
+    pub fn submit_solution(&mut self, template: Block, nonce: u32, tag: &[u8]) -> Result<Block, Status> {
+        let mut block: Block = template;
+        block.set_reward_tag(tag).map_err(|e| 
+            Status::new(rusty_leveldb::StatusCode::InvalidData, &format!("{e}"))
+        )?;
+        block.set_nonce(nonce);
 
-                // calculate new balances
-                let new_sender_balance: f32 = sender_balance - transaction.get_amount();
+        self.add_block(&block)?;
+        Ok(block)
+    }
 
-                balances.insert(sender, new_sender_balance);
-            }
+    /// Verifies that a block is valid to be put directly on top of the previous block.
+    /// The block's hash has to match its data and satisfy its difficulty, and that difficulty has to be
+    /// the one the retarget rule gives from the previous block and its retarget window, otherwise a miner 
+    /// could claim an easier difficulty than the one it was supposed to mine at.
+    /// 
+    /// # Arguments
+    /// * `prev_block` - A &Block which specifies a reference to the block directly below the block to verify
+    /// * `window_start_block` - A &Block which specifies a reference to the first block of the block's retarget window
+    /// * `block` - A &Block which specifies a reference to the block to verify
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the block is valid, or an error describing why it is not.
+    /// 
+    fn verify_block(prev_block: &Block, window_start_block: &Block, block: &Block) -> Result<(), Status> {
+        let supposed_difficulty: u32 = Block::get_supposed_difficulty(prev_block, window_start_block).ok_or(
+            Status::new(rusty_leveldb::StatusCode::InvalidData, "No block can come after the previous block")
+        )?;
 
-            // do same for recipient
-            let recipient_balance: f32 = *balances.get(&recipient).unwrap_or(
-                &self.get_balance(&recipient).unwrap_or(0.0)
-            );
-            
-            let new_recipient_balance: f32 = recipient_balance + transaction.get_amount();
-            
-            balances.insert(recipient, new_recipient_balance);
+        BlocksDB::verify_block_with_difficulty(block, supposed_difficulty)
+    }
+
+    /// Verifies a block on its own once the difficulty it's supposed to have is known, without looking at the chainstate.
+    /// 
+    /// # Arguments
+    /// * `block` - A &Block which specifies a reference to the block to verify
+    /// * `supposed_difficulty` - A u32 which specifies the difficulty the block has to have
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the block is valid, or an error if it is not.
+    /// 
+    pub(crate) fn verify_block_with_difficulty(block: &Block, supposed_difficulty: u32) -> Result<(), Status> {
+        // a block can't be on top of itself
+        if block.get_prev_hash() == block.get_hash() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Block prev hash is its own hash"));
         }
 
-        for (addr, balance) in balances.iter() {
-            if *balance < 0.0 {
-                return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, &format!("public key: {} has negative balance after all transactions.", addr)));
-            }
+        if !block.confirm_hash() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Block hash does not match block data"));
         }
 
-        Ok(balances)
+        if !block.confirm_difficulty() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Block hash does not satisfy block difficulty"));
+        }
+
+        // difficulty field could be tampered with to make the hash easier to find
+        if block.get_difficulty() != supposed_difficulty {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, 
+                &format!("Block difficulty {:08x} should be {:08x}", block.get_difficulty(), supposed_difficulty)));
+        }
+
+        if !block.confirm_merkel_root() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Block merkel root does not match its transactions"));
+        }
+
+        if !block.confirm_transactions() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Block has invalid transactions"));
+        }
+
+        Ok(())
     }
 
-    
-    /// Updates the chainstate with the transactions of a given block.
-    /// Multiple checks should be made before using this method. Method is private so as to not invalidate the data in the db
-    /// If error on update balance. Chainstate should be rebuilt from beginning
+    /// Verifies that a block is a valid genesis block, and that it's this network's genesis.
+    /// Genesis is exempt from proof of work since it has GENESIS_DIFFICULTY, which every hash satisfies.
+    /// Every field of genesis is fixed and its hash covers all of them, so it has to have the hash of Block::new_genesis.
     /// 
     /// # Arguments
-    /// * `transactions` - A Vec<Transaction> which specifies the transactions to update the chainstate with
-    /// 
-    /// # Modifications
-    /// This method changes often multiple addresses' balances using put on the db object.
+    /// * `block` - A &Block which specifies a reference to the block to verify
     /// 
     /// # Returns
-    /// An Result<(), Status> which is Ok(()) if the chainstate was successfully updated, or an error if it was not.
-    ///
-    fn update_chainstate(&mut self, transactions: Vec<Transaction>) -> Result<(), Status> {
-        // verify that the transactions are valid according to the chainstate
-        let verified_balances: HashMap<Point, f32> = self.verify_transactions(&transactions)?; 
+    /// An Result<(), Status> which is Ok(()) if the block is a valid genesis block, or an error if it is not.
+    /// 
+    pub(crate) fn verify_genesis(block: &Block) -> Result<(), Status> {
+        if block.get_height() != 0 || !block.confirm_hash() || !block.confirm_merkel_root() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Genesis block is invalid"));
+        }
 
-        // update all balances
-        for (addr, balance) in verified_balances.iter() {
-            self.update_balance(addr, *balance)?;
+        // genesis isn't mined, so it can't claim any other difficulty than the one that every hash satisfies
+        if block.get_difficulty() != GENESIS_DIFFICULTY {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, 
+                &format!("Genesis difficulty {:08x} should be {:08x}", block.get_difficulty(), GENESIS_DIFFICULTY)));
+        }
+
+        // a valid genesis with another timestamp is the start of another chain
+        if block.get_hash() != Block::new_genesis().get_hash() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Genesis block is not this network's genesis"));
         }
 
         Ok(())
     }
 
-    /// Method used to wipe the chainstate of every user and transaction.
+    /// Verifies that the chainstate started from this network's allocations under genesis.
+    /// The genesis allocations kept in the db have to be the expected ones, and undoing every block from the
+    /// chainstate has to give back exactly those balances, otherwise coins were added outside of any block.
+    /// 
+    /// # Arguments
+    /// * `allocations` - A &[(Point, f32)] which specifies the balances the network starts with under genesis
     /// 
     /// # Modifications
-    /// This method changes the internal state of the DB object by calling delete on it.
+    /// This method changes the internal state of the DB object by calling get and iterating over it.
     /// 
     /// # Returns
-    /// An Result<(), Status> which is Ok(()) if the chainstate was successfully cleared, or an error if it was not.
+    /// An Result<(), Status> which is Ok(()) if the initial chainstate is the expected one, or an error if it is not.
     /// 
-    fn clear_chainstate(&mut self) -> Result<(), Status> {
-        // make new db iterator
-        let mut iter: DBIterator = self.db.new_iter()?;
+    pub fn verify_initial_chainstate(&mut self, allocations: &[(Point, f32)]) -> Result<(), Status> {
+        let expected_balances: HashMap<Point, f32> = allocations.iter().cloned().collect();
+        let stored_balances: HashMap<Point, f32> = self.genesis_allocations()?.into_iter().collect();
 
-        // init values for key and val
-        let mut key: Vec<u8> = vec![];
-        let mut val: Vec<u8> = vec![];
+        if stored_balances != expected_balances {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Genesis allocations are not this network's allocations"));
+        }
 
-        while iter.advance() {
-            // get current key and val
-            iter.current(&mut key, &mut val);
-            
-            // verify prefix
-            if key.len() >= 7 && key[0..7] == *PUBLIC_KEY_PREFIX {
-                // wipe key
-                self.db.delete(&key)?;
+        for (public_key, balance) in self.balances_at(0)? {
+            let expected_balance: f32 = *expected_balances.get(&public_key).unwrap_or(&0.0);
+
+            // undoing the blocks in f32 can be off by a rounding error
+            if (balance - expected_balance).abs() > 1e-3 {
+                return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, 
+                    &format!("public key: {} has {} under genesis but was allocated {}", public_key, balance, expected_balance)));
             }
         }
 
         Ok(())
     }
 
-    /// Method used to rebuild chainstate from all the blocks in the db.
-    /// Used in case when chainstate gets updated, there are some internal errors and the data gets corrupted
+    /// Verifies that a block is directly on top of the previous block, one height above it and pointing to its hash.
     /// 
-    /// # Modifications
-    /// This method changes the internal state of the DB object by calling get and put on it.
+    /// # Arguments
+    /// * `prev_block` - A &Block which specifies a reference to the block that should be directly below the block
+    /// * `block` - A &Block which specifies a reference to the block to verify
     /// 
     /// # Returns
-    /// An Result<(), Status> which is Ok(()) if the chainstate was successfully rebuilt, or an error if it was not.
+    /// An Result<(), Status> which is Ok(()) if the block links to the previous block, or an error if it does not.
     /// 
-    pub fn rebuild_chainstate(&mut self) -> Result<(), Status> {
+    pub(crate) fn verify_link(prev_block: &Block, block: &Block) -> Result<(), Status> {
+        if block.get_height() != prev_block.get_height() + 1 || block.get_prev_hash() != prev_block.get_hash() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Block does not link to the block below it"));
+        }
 
-        // clear chainstate
-        self.clear_chainstate()?;
+        Ok(())
+    }
 
-        let mut curr_block: Block; 
-        let mut curr_height: u64 = 0; // start at genesis block
+    /// Obtains how many blocks of a chain are valid from genesis up, before the first invalid block.
+    /// Used to partially accept a chain that was received from an untrusted source.
+    /// 
+    /// # Arguments
+    /// * `blocks` - A &[Block] which specifies the chain to check, ordered by height starting from genesis
+    /// 
+    /// # Returns
+    /// A u64 which is the number of valid blocks at the start of the chain, so also the height of the first invalid block.
+    /// 
+    pub fn valid_prefix_length(blocks: &[Block]) -> u64 {
+        let mut valid_length: u64 = 0;
 
-        let latest_block: Block = self.get_latest_block()?;
-        let latest_block_height: u64 = latest_block.get_height();
-    
+        for (i, block) in blocks.iter().enumerate() {
+            let verified: Result<(), Status> = if i == 0 {
+                BlocksDB::verify_genesis(block)
+            } else {
+                // blocks are linked from genesis up to here, so a block's index is its height
+                let window_start_block: &Block = &blocks[get_retarget_window_start(i as u64) as usize];
 
-        while curr_height <= latest_block_height {
-            // get block
-            curr_block = self.get_block(curr_height)?;
+                BlocksDB::verify_link(&blocks[i - 1], block)
+                    .and_then(|_| BlocksDB::verify_block(&blocks[i - 1], window_start_block, block))
+            };
 
-            // update chainstate
-            let transactions: Vec<Transaction> = curr_block.get_transactions();
-            self.update_chainstate(transactions)?;
+            if verified.is_err() {
+                break;
+            }
 
-        
-            curr_height += 1;
+            valid_length += 1;
         }
 
-        Ok(())
+        valid_length
     }
+
+    /// Validates every block in the db from genesis up to the latest block.
+    /// Each block has to be stored at its own height, link to the block below it and pass the same checks as when it is added.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the whole chain is valid, or an error describing the first invalid block.
+    /// 
+    pub fn validate_chain(&mut self) -> Result<(), Status> {
+        match self.get_chain_issues().into_iter().next() {
+            Some(issue) => Err(issue),
+            None => Ok(())
+        }
+    }
+
+    /// Opens the db in a directory and validates the whole chain in it, as a diagnostic for a node's data.
+    /// 
+    /// # Arguments
+    /// * `dir` - A &Path which specifies the directory of the db to verify
+    /// 
+    /// # Returns
+    /// An Result<(), Vec<Status>> which is Ok(()) if the chain is valid, or every problem found in it if it is not.
+    /// 
+    pub fn verify_dir(dir: &Path) -> Result<(), Vec<Status>> {
+        let mut db: BlocksDB = BlocksDB::open(dir).map_err(|e| vec![e])?;
+
+        let issues: Vec<Status> = db.get_chain_issues();
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+
+        Ok(())
+    }
+
+    /// Walks every block in the db from genesis up to the latest block and collects everything wrong with them.
+    /// A block that can't be read is reported and the block above it can't be linked, but the walk keeps going.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// A Vec<Status> of every issue found in the chain, which is empty if the chain is valid.
+    /// 
+    fn get_chain_issues(&mut self) -> Vec<Status> {
+        let mut issues: Vec<Status> = Vec::new();
+
+        let latest_block: Block = match self.get_latest_block() {
+            Ok(block) => block,
+            Err(e) => {
+                issues.push(e.annotate("Latest block"));
+                return issues;
+            }
+        };
+
+        // last block that could be read, None if the block below the current one couldn't be
+        let mut prev_block: Option<Block> = None;
+
+        for height in 0..=latest_block.get_height() {
+            let block: Block = match self.get_block(height) {
+                Ok(block) => block,
+                Err(e) => {
+                    issues.push(e.annotate(format!("Block at height {}", height)));
+                    prev_block = None;
+                    continue;
+                }
+            };
+
+            if height == 0 {
+                if let Err(e) = BlocksDB::verify_genesis(&block) {
+                    issues.push(e);
+                }
+            } else if let Some(prev_block) = &prev_block {
+                if let Err(e) = BlocksDB::verify_link(prev_block, &block) {
+                    issues.push(e.annotate(format!("Block at height {}", height)));
+                }
+
+                let verified: Result<(), Status> = self.get_block(get_retarget_window_start(height))
+                    .and_then(|window_start_block| BlocksDB::verify_block(prev_block, &window_start_block, &block));
+
+                if let Err(e) = verified {
+                    issues.push(e.annotate(format!("Block at height {}", height)));
+                }
+            }
+
+            prev_block = Some(block);
+        }
+
+        // latest block has to be the top of the chain that was just walked
+        if let Some(top_block) = prev_block {
+            if top_block.get_hash() != latest_block.get_hash() {
+                issues.push(Status::new(rusty_leveldb::StatusCode::InvalidData, "Latest block is not the top of the chain"));
+            }
+        }
+
+        // the chainstate has to undo back to the allocations under genesis
+        let initial_chainstate: Result<(), Status> = self.genesis_allocations()
+            .and_then(|allocations| self.verify_initial_chainstate(&allocations));
+
+        if let Err(e) = initial_chainstate {
+            issues.push(e.annotate("Initial chainstate"));
+        }
+
+        issues
+    }
+
+    /// Validates the whole chain and checks that its latest block is the expected one,
+    /// for nodes bootstrapping from a trusted source that only gives out the tip hash.
+    /// 
+    /// # Arguments
+    /// * `expected_tip` - A &str which specifies the hash the latest block is expected to have
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// A bool which is true if the chain is valid and ends at the expected tip, false otherwise.
+    /// 
+    pub fn validate_against_tip(&mut self, expected_tip: &str) -> bool {
+        if self.validate_chain().is_err() {
+            return false;
+        }
+
+        match self.get_latest_block() {
+            Ok(latest_block) => latest_block.get_hash() == expected_tip,
+            Err(_) => false
+        }
+    }
+
+    /// Reads and returns the balance of a given adress.
+    /// 
+    /// # Arguments
+    /// * `public_key` - A &Point which specifies a reference to the public key to lookup
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Option<f32> which is the balance of the address if it exists in the db, or None if it does not.
+    /// 
+    pub fn get_balance(&mut self, public_key: &Point) -> Result<f32, Status> {
+
+        // serialize the public_key to get the database key. Unwrap because Point never fails to serialize
+        match self.db.get(&BlocksDB::get_db_user_key(public_key)) {
+            Some(bytes) => {
+
+                // wrap bytes buffer with a cursor for easy little-endian conversion to f32
+                let mut reader: Cursor<Vec<u8>> = Cursor::new(bytes);
+
+                Ok(reader.read_f32::<LittleEndian>()?) // Error if data is corrupted
+            },
+            None => {
+                // address was not found
+                Err(Status::new(rusty_leveldb::StatusCode::NotFound, &format!("Public key was not found.")))
+            } 
+        }
+    }
+
+    /// Obtains the balance of an address without the miner rewards it got that don't have COINBASE_MATURITY confirmations yet,
+    /// so a wallet doesn't show coins that could still disappear with their block. An address that isn't found has nothing.
+    /// 
+    /// # Arguments
+    /// * `public_key` - A &Point which specifies a reference to the public key to get the spendable balance of
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f32, Status> which is the balance minus the immature rewards (0 if they were already spent), or an error if the blocks could not be read.
+    /// 
+    pub fn spendable_balance_of(&mut self, public_key: &Point) -> Result<f32, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+        let balance: f32 = self.get_balance(public_key).unwrap_or(0.0);
+
+        // the latest block has 1 confirmation, genesis has no reward
+        let first_immature_height: u64 = (latest_block_height + 1).saturating_sub(COINBASE_MATURITY - 1).max(1);
+
+        let mut immature_rewards: f32 = 0.0;
+        for height in first_immature_height..=latest_block_height {
+            if let Some(reward) = self.get_block(height)?.get_reward_transaction() {
+                if reward.get_recipient() == *public_key {
+                    immature_rewards += reward.get_amount();
+                }
+            }
+        }
+
+        Ok((balance - immature_rewards).max(0.0))
+    }
+
+    /// Method used to update balance for an existing user or create a new user with a specified balance.
+    /// 
+    /// # Arguments
+    /// * `public_key` - A &Point which specifies a reference to the public key to update the balance of
+    /// * `value` - A f32 which specifies the value to update the balance to
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling put on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the balance was successfully updated, or an error if it was not.
+    /// 
+    fn update_balance(&mut self, public_key: &Point, value: f32) -> Result<(), Status> {
+        // account balances are stored in little-endian
+        self.db.put(&BlocksDB::get_db_user_key(public_key), &value.to_le_bytes())?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+
+    /// Method to add prefix to the user public keys to get the key in the db
+    /// 
+    /// # Arguments
+    /// * `public_key` - A &Point which specifies a reference to the public key to get the db key for
+    /// 
+    /// # Returns
+    /// A Vec<u8> which is the key in the db for the public key
+    /// 
+    fn get_db_user_key(public_key: &Point) -> Vec<u8> {
+        // add prefix
+        let mut key: Vec<u8> = Vec::new();
+        key.extend_from_slice(PUBLIC_KEY_PREFIX);
+        key.extend_from_slice(&bincode::serialize(public_key).unwrap());
+
+        key
+    }
+
+    
+    /// Obtains every address that has held a balance, which is every sender and recipient the chainstate has seen.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by iterating over it.
+    /// 
+    /// # Returns
+    /// An Result<HashSet<Point>, Status> which is the set of public keys in the chainstate, or an error if one of them is corrupt.
+    /// 
+    pub fn known_addresses(&mut self) -> Result<HashSet<Point>, Status> {
+        let mut addresses: HashSet<Point> = HashSet::new();
+
+        let mut iter: DBIterator = self.db.new_iter()?;
+
+        let mut key: Vec<u8> = vec![];
+        let mut val: Vec<u8> = vec![];
+
+        while iter.advance() {
+            iter.current(&mut key, &mut val);
+
+            // chainstate keys are the prefix followed by the serialized public key
+            if key.len() >= 7 && key[0..7] == *PUBLIC_KEY_PREFIX {
+                let public_key: Point = bincode::deserialize(&key[7..]).map_err(|e| 
+                    Status::new(rusty_leveldb::StatusCode::Corruption, &format!("{e}"))
+                )?;
+
+                addresses.insert(public_key);
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    /// verifies that the transactions are valid and can be added to the chainstate.
+    /// makes a hashmap of all the new balances so that the new balances can be updated easily
+    /// 
+    /// # Arguments
+    /// * `transactions` - A Vec<Transaction> which specifies the transactions to verify
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<HashMap<&Point, f32>, Status> which is returns a map of the new balances of the verified transactions
+    /// 
+    pub fn verify_transactions(&mut self, transactions: &Vec<Transaction>) -> Result<HashMap<Point, f32>, Status> {
+        self.verify_transactions_from(transactions, None)
+    }
+
+    /// verifies that the transactions are valid against the chainstate as it was at a height instead of the current one,
+    /// to validate a historical block again. the block at height is counted, so a block is verified at the height below it
+    /// 
+    /// # Arguments
+    /// * `transactions` - A &Vec<Transaction> which specifies the transactions to verify
+    /// * `height` - A u64 which specifies the height of the last block in the chainstate to verify against
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get and iterating over it.
+    /// 
+    /// # Returns
+    /// An Result<HashMap<Point, f32>, Status> which is the new balances of the verified transactions, or an error if they are invalid at that height.
+    /// 
+    pub fn verify_transactions_at(&mut self, transactions: &Vec<Transaction>, height: u64) -> Result<HashMap<Point, f32>, Status> {
+        let snapshot: HashMap<Point, f32> = self.balances_at(height)?;
+
+        self.verify_transactions_from(transactions, Some(&snapshot))
+    }
+
+    /// Obtains the balance of every known address as it was once the block at a height was added.
+    /// The balances under genesis aren't in any block, so instead of replaying the chain from genesis
+    /// the blocks above height are undone from the current chainstate, from the latest block down.
+    /// 
+    /// # Arguments
+    /// * `height` - A u64 which specifies the height of the last block to count
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get and iterating over it.
+    /// 
+    /// # Returns
+    /// An Result<HashMap<Point, f32>, Status> which is the balances at that height, or an error if the height is above the latest block or the blocks could not be read.
+    /// 
+    pub fn balances_at(&mut self, height: u64) -> Result<HashMap<Point, f32>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if height > latest_block_height {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidArgument, &format!("Height {} is above the latest block", height)));
+        }
+
+        let mut balances: HashMap<Point, f32> = HashMap::new();
+        for address in self.known_addresses()? {
+            let balance: f32 = self.get_balance(&address)?;
+            balances.insert(address, balance);
+        }
+
+        for undone_height in (height + 1..=latest_block_height).rev() {
+            let block: Block = self.get_block(undone_height)?;
+
+            // undone in reverse, the opposite of what verify_transactions does
+            for transaction in block.get_transactions().iter().rev() {
+                *balances.entry(transaction.get_recipient()).or_insert(0.0) -= transaction.get_amount();
+
+                // Point::identity is miner reward
+                if transaction.get_sender() != Point::identity() {
+                    *balances.entry(transaction.get_sender()).or_insert(0.0) += transaction.get_amount() + transaction.get_fee();
+                }
+            }
+        }
+
+        Ok(balances)
+    }
+
+    /// verifies that the transactions are valid against either the chainstate or a snapshot of it.
+    /// 
+    /// # Arguments
+    /// * `transactions` - A &Vec<Transaction> which specifies the transactions to verify
+    /// * `snapshot` - An Option<&HashMap<Point, f32>> which specifies the balances to start from, or None for the chainstate's
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<HashMap<Point, f32>, Status> which is returns a map of the new balances of the verified transactions
+    /// 
+    fn verify_transactions_from(&mut self, transactions: &Vec<Transaction>, snapshot: Option<&HashMap<Point, f32>>) -> Result<HashMap<Point, f32>, Status> {
+        // hashmap to remember good balances
+        let mut balances: HashMap<Point, f32> = HashMap::new();
+
+        for transaction in transactions {
+            let sender: Point = transaction.get_sender();
+            let recipient: Point = transaction.get_recipient();
+
+            // Point::identity is miner reward
+            if sender != Point::identity() {
+
+                // get original balances
+                // check hashmap first for balances
+                let sender_balance: f32 = *balances.get(&sender).unwrap_or(
+                    &self.get_starting_balance(&sender, snapshot)
+                );
+
+                // calculate new balances, the sender also pays the fee which the miner gets in its reward
+                let new_sender_balance: f32 = sender_balance - transaction.get_amount() - transaction.get_fee();
+
+                balances.insert(sender, new_sender_balance);
+            }
+
+            // do same for recipient
+            let recipient_balance: f32 = *balances.get(&recipient).unwrap_or(
+                &self.get_starting_balance(&recipient, snapshot)
+            );
+            
+            let new_recipient_balance: f32 = recipient_balance + transaction.get_amount();
+            
+            balances.insert(recipient, new_recipient_balance);
+        }
+
+        for (addr, balance) in balances.iter() {
+            if *balance < 0.0 {
+                return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, &format!("public key: {} has negative balance after all transactions.", addr)));
+            }
+        }
+
+        // a miner reward can't be spent in the block that it's in, the miner has to afford its transactions without it
+        for transaction in transactions {
+            if transaction.get_sender() == Point::identity() {
+                let recipient: Point = transaction.get_recipient();
+
+                if balances[&recipient] - transaction.get_amount() < 0.0 {
+                    return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, &format!("public key: {} spends its reward in the same block.", recipient)));
+                }
+            }
+        }
+
+        Ok(balances)
+    }
+
+    /// returns the balance an address starts with before the transactions, from the snapshot if there is one or from the chainstate otherwise.
+    /// an address that isn't found has nothing
+    fn get_starting_balance(&mut self, public_key: &Point, snapshot: Option<&HashMap<Point, f32>>) -> f32 {
+        match snapshot {
+            Some(snapshot) => *snapshot.get(public_key).unwrap_or(&0.0),
+            None => self.get_balance(public_key).unwrap_or(0.0)
+        }
+    }
+
+    
+    /// Updates the chainstate with the transactions of a given block.
+    /// Multiple checks should be made before using this method. Method is private so as to not invalidate the data in the db
+    /// If error on update balance. Chainstate should be rebuilt from beginning
+    /// 
+    /// # Arguments
+    /// * `transactions` - A Vec<Transaction> which specifies the transactions to update the chainstate with
+    /// 
+    /// # Modifications
+    /// This method changes often multiple addresses' balances using put on the db object.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the chainstate was successfully updated, or an error if it was not.
+    ///
+    fn update_chainstate(&mut self, transactions: Vec<Transaction>) -> Result<(), Status> {
+        // verify that the transactions are valid according to the chainstate
+        let verified_balances: HashMap<Point, f32> = self.verify_transactions(&transactions)?; 
+
+        // update all balances
+        for (addr, balance) in verified_balances.iter() {
+            self.update_balance(addr, *balance)?;
+        }
+
+        Ok(())
+    }
+
+    /// Method used to wipe the chainstate of every user and transaction.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling delete on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the chainstate was successfully cleared, or an error if it was not.
+    /// 
+    fn clear_chainstate(&mut self) -> Result<(), Status> {
+        // make new db iterator
+        let mut iter: DBIterator = self.db.new_iter()?;
+
+        // init values for key and val
+        let mut key: Vec<u8> = vec![];
+        let mut val: Vec<u8> = vec![];
+
+        while iter.advance() {
+            // get current key and val
+            iter.current(&mut key, &mut val);
+            
+            // verify prefix
+            if key.len() >= 7 && key[0..7] == *PUBLIC_KEY_PREFIX {
+                // wipe key
+                self.db.delete(&key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method used to rebuild chainstate from the genesis allocations and all the blocks in the db.
+    /// Used in case when chainstate gets updated, there are some internal errors and the data gets corrupted
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get and put on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the chainstate was successfully rebuilt, or an error if it was not.
+    /// 
+    pub fn rebuild_chainstate(&mut self) -> Result<(), Status> {
+        let allocations: Vec<(Point, f32)> = self.genesis_allocations()?;
+
+        // clear chainstate
+        self.clear_chainstate()?;
+
+        // start from the balances under genesis instead of nothing
+        for (public_key, balance) in allocations.iter() {
+            self.update_balance(public_key, *balance)?;
+        }
+
+        let mut curr_block: Block; 
+        let mut curr_height: u64 = 0; // start at genesis block
+
+        let latest_block: Block = self.get_latest_block()?;
+        let latest_block_height: u64 = latest_block.get_height();
+    
+
+        while curr_height <= latest_block_height {
+            // get block
+            curr_block = self.get_block(curr_height)?;
+
+            // update chainstate
+            let transactions: Vec<Transaction> = curr_block.get_transactions();
+            self.update_chainstate(transactions)?;
+
+        
+            curr_height += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Obtains how old the chain is, the time between the genesis block and the latest block.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<u64, Status> which is the age of the chain in seconds, or an error if the blocks could not be read.
+    /// 
+    pub fn chain_age_seconds(&mut self) -> Result<u64, Status> {
+        let genesis: Block = self.get_block(0)?;
+        let latest_block: Block = self.get_latest_block()?;
+
+        Ok(latest_block.get_timestamp().saturating_sub(genesis.get_timestamp()))
+    }
+
+    /// Obtains how old a block is compared to the whole chain, from 0 for the latest block to 1 for genesis.
+    /// 
+    /// # Arguments
+    /// * `height` - A u64 which specifies the height of the block
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Option<f64>, Status> which is the block's age over the chain's age, or None for heights above the latest block and a chain with no age.
+    /// 
+    pub fn relative_age(&mut self, height: u64) -> Result<Option<f64>, Status> {
+        let latest_block: Block = self.get_latest_block()?;
+
+        if height > latest_block.get_height() {
+            return Ok(None);
+        }
+
+        let chain_age: u64 = self.chain_age_seconds()?;
+        if chain_age == 0 {
+            return Ok(None);
+        }
+
+        // same as chain_age_seconds, a timestamp after the latest block's counts as no age
+        let block_age: u64 = latest_block.get_timestamp().saturating_sub(self.get_block(height)?.get_timestamp());
+
+        Ok(Some(block_age as f64 / chain_age as f64))
+    }
+
+    /// Obtains how long ago the latest block was mined, to know if the chain is still moving.
+    /// 
+    /// # Arguments
+    /// * `clock` - A &impl Clock which specifies what time it is
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<u64, Status> which is the seconds since the latest block's timestamp (0 if it is in the future), or an error if the latest block could not be read.
+    /// 
+    pub fn seconds_since_tip(&mut self, clock: &impl Clock) -> Result<u64, Status> {
+        let latest_block: Block = self.get_latest_block()?;
+
+        Ok(clock.now().saturating_sub(latest_block.get_timestamp()))
+    }
+
+    /// Decides if the chain has stalled, when no block was mined for STALE_TIP_BLOCKS times the block speed.
+    /// 
+    /// # Arguments
+    /// * `clock` - A &impl Clock which specifies what time it is
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<bool, Status> which is true if the chain has stalled, or an error if the latest block could not be read.
+    /// 
+    pub fn is_stale(&mut self, clock: &impl Clock) -> Result<bool, Status> {
+        Ok(self.seconds_since_tip(clock)? > STALE_TIP_BLOCKS * BLOCK_SPEED)
+    }
+
+    /// Obtains the average time between two blocks over the whole chain.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the average block interval in seconds (0 if there is only genesis), or an error if the blocks could not be read.
+    /// 
+    pub fn average_block_interval(&mut self) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if latest_block_height == 0 {
+            return Ok(0.0);
+        }
+
+        Ok(self.chain_age_seconds()? as f64 / latest_block_height as f64)
+    }
+
+    /// Obtains the size of the whole chain in bytes, as it is encoded by Block::to_bytes.
+    /// The db stores blocks in that encoding so the stored bytes are only measured, no block is deserialized or serialized.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<usize, Status> which is the total size of the blocks in bytes, or an error if a block is missing.
+    /// 
+    pub fn total_serialized_size(&mut self) -> Result<usize, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut total_bytes: usize = 0;
+        for height in 0..=latest_block_height {
+            match self.db.get(&height.to_le_bytes()) {
+                Some(bytes) => total_bytes += bytes.len(),
+                None => return Err(Status::new(rusty_leveldb::StatusCode::NotFound, &format!("Block at height {} not found", height)))
+            }
+        }
+
+        Ok(total_bytes)
+    }
+
+    /// Obtains the fraction of all the blocks, genesis included, that are empty, which are the blocks with only their reward.
+    /// Genesis has no reward so it's never empty, but it still counts as a block.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the ratio of empty blocks between 0 and 1 (0 if there is only genesis), or an error if the blocks could not be read.
+    /// 
+    pub fn empty_block_ratio(&mut self) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut empty_blocks: u64 = 0;
+        for height in 0..=latest_block_height {
+            let block: Block = self.get_block(height)?;
+
+            if block.get_transaction_count() == 1 && block.get_reward_transaction().is_some() {
+                empty_blocks += 1;
+            }
+        }
+
+        Ok(empty_blocks as f64 / (latest_block_height + 1) as f64)
+    }
+
+    /// Obtains the average difficulty over the whole chain, from genesis to the latest block.
+    /// Difficulties are masks so they are averaged by the expected attempts to mine them, genesis counting as a single attempt.
+    /// The blocks are read one at a time so the chain is never held in memory.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the average expected attempts per block, or an error if the blocks could not be read.
+    /// 
+    pub fn average_difficulty(&mut self) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut total_attempts: f64 = 0.0;
+        for height in 0..=latest_block_height {
+            total_attempts += self.get_block(height)?.expected_attempts() as f64;
+        }
+
+        Ok(total_attempts / (latest_block_height + 1) as f64)
+    }
+
+    /// Obtains the total work of the chain, the expected attempts it took to mine every block from genesis to the latest block.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<u128, Status> which is the total expected attempts of the chain, or an error if the blocks could not be read.
+    /// 
+    pub fn total_work(&mut self) -> Result<u128, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut total_attempts: u128 = 0;
+        for height in 0..=latest_block_height {
+            total_attempts += self.get_block(height)?.expected_attempts();
+        }
+
+        Ok(total_attempts)
+    }
+
+    /// Obtains the share of the chain's total work that a miner did, the expected attempts of the blocks it was rewarded for over all of them.
+    /// Harder blocks count for more, so it's the miner's share of the hash rate rather than of the blocks.
+    /// 
+    /// # Arguments
+    /// * `address` - A &Point which specifies the public key the miner gets its rewards at
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the miner's share of the work between 0 and 1, or an error if the blocks could not be read.
+    /// 
+    pub fn miner_work_share(&mut self, address: &Point) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut total_attempts: u128 = 0;
+        let mut miner_attempts: u128 = 0;
+
+        for height in 0..=latest_block_height {
+            let block: Block = self.get_block(height)?;
+            let attempts: u128 = block.expected_attempts();
+
+            // genesis has no reward so nobody mined it
+            if block.get_reward_transaction().is_some_and(|reward| reward.get_recipient() == *address) {
+                miner_attempts += attempts;
+            }
+
+            total_attempts += attempts;
+        }
+
+        Ok(miner_attempts as f64 / total_attempts as f64)
+    }
+
+    /// Verifies that the chain has the total work a peer claimed it has, so a peer can't advertise more work than it sent.
+    /// 
+    /// # Arguments
+    /// * `claimed` - A u128 which specifies the total work the peer claimed, as in its HandshakeInfo
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<bool, Status> which is true if the chain's total work is the claimed one, or an error if the blocks could not be read.
+    /// 
+    pub fn verify_total_work(&mut self, claimed: u128) -> Result<bool, Status> {
+        Ok(self.total_work()? == claimed)
+    }
+
+    /// Obtains a fingerprint of the whole chain to quickly check if another node has the same chain.
+    /// Block hashes are chained into it from genesis up, so a single different block changes it.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<String, Status> which is the hash of the chain's block hashes, or an error if the blocks could not be read.
+    /// 
+    pub fn chain_fingerprint(&mut self) -> Result<String, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut fingerprint: String = String::new();
+        for height in 0..=latest_block_height {
+            fingerprint = hash(fingerprint + &self.get_block(height)?.get_hash());
+        }
+
+        Ok(fingerprint)
+    }
+
+    /// Obtains the tip info of the chain to send to a node when connecting to it.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<HandshakeInfo, Status> which is the height, hash and total work of the chain, or an error if the blocks could not be read.
+    /// 
+    pub fn handshake_info(&mut self) -> Result<HandshakeInfo, Status> {
+        let latest_block: Block = self.get_latest_block()?;
+        let total_work: u128 = self.total_work()?;
+
+        Ok(HandshakeInfo::new(latest_block.get_height(), latest_block.get_hash(), total_work))
+    }
+
+    /// Obtains the difficulty of every block of the chain, from genesis to the latest block, to chart it over time.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Vec<(u64, u32)>, Status> which is the (height, difficulty) of every block ordered by height, or an error if the blocks could not be read.
+    /// 
+    pub fn difficulty_series(&mut self) -> Result<Vec<(u64, u32)>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        let mut series: Vec<(u64, u32)> = Vec::new();
+        for height in 0..=latest_block_height {
+            series.push((height, self.get_block(height)?.get_difficulty()));
+        }
+
+        Ok(series)
+    }
+
+    /// Obtains the average time between two blocks over the whole chain, weighted by the expected attempts to mine each block.
+    /// Harder blocks weigh more, so the average isn't pulled by the many fast blocks mined when the difficulty was low.
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the weighted average block interval in seconds (0 if there is only genesis), or an error if the blocks could not be read.
+    /// 
+    pub fn work_weighted_block_time(&mut self) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if latest_block_height == 0 {
+            return Ok(0.0);
+        }
+
+        let mut prev_timestamp: u64 = self.get_block(0)?.get_timestamp();
+        let mut weighted_intervals: f64 = 0.0;
+        let mut total_attempts: f64 = 0.0;
+
+        for height in 1..=latest_block_height {
+            let block: Block = self.get_block(height)?;
+            let attempts: f64 = block.expected_attempts() as f64;
+
+            // same as chain_age_seconds, a timestamp below the previous one counts as no time
+            weighted_intervals += block.get_timestamp().saturating_sub(prev_timestamp) as f64 * attempts;
+            total_attempts += attempts;
+
+            prev_timestamp = block.get_timestamp();
+        }
+
+        Ok(weighted_intervals / total_attempts)
+    }
+
+    /// Obtains by how much the difficulty changed at a given block compared to the block below it, in percent.
+    /// The difficulty only changes at retarget heights, and it's measured by the expected attempts of the
+    /// difficulty mask, so a positive change means the block was harder to mine.
+    ///
+    /// # Arguments
+    /// * `height` - A u64 which specifies the height of the block
+    ///
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    ///
+    /// # Returns
+    /// An Result<Option<f64>, Status> which is the percent change, or None for genesis and heights above the latest block.
+    ///
+    pub fn difficulty_change_at(&mut self, height: u64) -> Result<Option<f64>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if height == 0 || height > latest_block_height {
+            return Ok(None);
+        }
+
+        let prev_attempts: f64 = self.get_block(height - 1)?.expected_attempts() as f64;
+        let attempts: f64 = self.get_block(height)?.expected_attempts() as f64;
+
+        Ok(Some((attempts / prev_attempts - 1.0) * 100.0))
+    }
+
+    /// Obtains the reward the miner of a block got, REWARD plus the fees of the block's transactions.
+    /// It's read from the block's reward transaction, which confirm_transactions already checked.
+    /// 
+    /// # Arguments
+    /// * `height` - A u64 which specifies the height of the block
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Option<f32>, Status> which is the reward, or None for genesis and heights above the latest block.
+    /// 
+    pub fn block_reward(&mut self, height: u64) -> Result<Option<f32>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if height > latest_block_height {
+            return Ok(None);
+        }
+
+        // genesis has no reward transaction
+        Ok(self.get_block(height)?.get_reward_transaction().map(|reward| reward.get_amount()))
+    }
+
+    /// Obtains the median fee rate of the transactions in the last blocks, in fee per serialized byte.
+    /// Used by wallets to pick a fee, so miner rewards aren't counted since they don't pay a fee.
+    /// 
+    /// # Arguments
+    /// * `window` - A u64 which specifies how many blocks to take, from the latest block down
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the median fee rate, or 0 if the blocks have no transactions other than rewards.
+    /// 
+    pub fn median_fee_rate(&mut self, window: u64) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+        let start: u64 = (latest_block_height + 1).saturating_sub(window);
+
+        let mut fee_rates: Vec<f64> = Vec::new();
+        for block in self.get_block_range(start, latest_block_height + 1)? {
+            for transaction in block.get_transactions() {
+                if transaction.get_sender() == Point::identity() {
+                    continue;
+                }
+
+                // unwrap since transactions always serialize
+                let transaction_bytes: u64 = bincode::serialized_size(&transaction).unwrap();
+                fee_rates.push(transaction.get_fee() as f64 / transaction_bytes as f64);
+            }
+        }
+
+        if fee_rates.is_empty() {
+            return Ok(0.0);
+        }
+
+        // fees are validated to be finite so fee rates always compare
+        fee_rates.sort_by(|fee_rate, other_fee_rate| fee_rate.total_cmp(other_fee_rate));
+
+        let middle: usize = fee_rates.len() / 2;
+        if fee_rates.len().is_multiple_of(2) {
+            Ok((fee_rates[middle - 1] + fee_rates[middle]) / 2.0)
+        } else {
+            Ok(fee_rates[middle])
+        }
+    }
+
+    /// Obtains the effective time of a block, the median time past of the MEDIAN_TIME_SPAN blocks before it.
+    /// A single block's timestamp can be off, the median of several of them can't be moved by one miner.
+    /// Close to genesis the median is taken over the blocks that exist, and genesis has nothing before it so it has its own timestamp.
+    /// 
+    /// # Arguments
+    /// * `height` - A u64 which specifies the height of the block
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Option<u64>, Status> which is the median timestamp, or None if the height is above the latest block.
+    /// 
+    pub fn effective_time(&mut self, height: u64) -> Result<Option<u64>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if height > latest_block_height {
+            return Ok(None);
+        }
+
+        if height == 0 {
+            return Ok(Some(self.get_block(0)?.get_timestamp()));
+        }
+
+        // the block's own timestamp isn't in the window
+        let start: u64 = height.saturating_sub(MEDIAN_TIME_SPAN);
+        let timestamps: Vec<u64> = self.get_block_range(start, height)?.iter()
+            .map(|block| block.get_timestamp())
+            .collect();
+
+        Ok(Some(median_timestamp(timestamps)))
+    }
+
+    /// Obtains how far along the node is in downloading the chain, compared to the best height advertised by peers.
+    /// 
+    /// # Arguments
+    /// * `best_known_height` - A u64 which specifies the highest block height known from peers
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<f64, Status> which is the progress between 0 and 1, or an error if the latest block could not be read.
+    /// 
+    pub fn sync_progress(&mut self, best_known_height: u64) -> Result<f64, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        // only genesis is known, so there is nothing to download
+        if best_known_height == 0 {
+            return Ok(1.0);
+        }
+
+        Ok((latest_block_height as f64 / best_known_height as f64).clamp(0.0, 1.0))
+    }
+
+    /// Looks for a transaction in the blocks of the db, starting from the latest block since recent transactions are the most looked up.
+    /// 
+    /// # Arguments
+    /// * `transaction_hash` - A &str which specifies the hash of the transaction to look for
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Option<u64>, Status> which is the height of the block containing the transaction, or None if no block contains it.
+    /// 
+    pub fn find_transaction(&mut self, transaction_hash: &str) -> Result<Option<u64>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        for height in (0..=latest_block_height).rev() {
+            let block: Block = self.get_block(height)?;
+
+            if block.get_transactions().iter().any(|transaction| transaction.get_hash() == transaction_hash) {
+                return Ok(Some(height));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Obtains how many confirmations a transaction had as of a given height, as if that height was the latest block.
+    /// The block containing the transaction counts as the first confirmation.
+    /// 
+    /// # Arguments
+    /// * `transaction_hash` - A &str which specifies the hash of the transaction
+    /// * `as_of_height` - A u64 which specifies the height to count the confirmations at
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<Option<u64>, Status> which is the number of confirmations, or None if no block up to that height contains the transaction
+    /// or if the height is above the latest block.
+    /// 
+    pub fn confirmation_depth_at(&mut self, transaction_hash: &str, as_of_height: u64) -> Result<Option<u64>, Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        if as_of_height > latest_block_height {
+            return Ok(None);
+        }
+
+        for height in (0..=as_of_height).rev() {
+            let block: Block = self.get_block(height)?;
+
+            if block.get_transactions().iter().any(|transaction| transaction.get_hash() == transaction_hash) {
+                return Ok(Some(as_of_height - height + 1));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Obtains the transactions confirmed since a given height, which are the transactions of every block above it.
+    ///
+    /// # Arguments
+    /// * `old_height` - A u64 which specifies the height of the last block that was already known
+    ///
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    ///
+    /// # Returns
+    /// An Result<Vec<(u64, Transaction)>, Status> which is every new transaction with the height of its block, ordered by height.
+    ///
+    pub fn transactions_added_since(&mut self, old_height: u64) -> Result<Vec<(u64, Transaction)>, Status> {
+        let mut transactions: Vec<(u64, Transaction)> = Vec::new();
+
+        for block in self.get_block_range(old_height.saturating_add(1), u64::MAX)? {
+            for transaction in block.get_transactions() {
+                transactions.push((block.get_height(), transaction));
+            }
+        }
+
+        Ok(transactions)
+    }
+
+    /// Recovers the db after the node stopped in the middle of writing a block.
+    /// Walks the blocks from genesis up to the latest block until one is missing or corrupt, drops every block above the last good one,
+    /// and makes the last good block the latest block so that the chain loads up to it instead of failing entirely.
+    /// The chainstate could have been updated with the dropped blocks, so it's replayed from the genesis allocations up to the last good block.
+    /// 
+    /// # Modifications
+    /// This method can delete blocks, update the latest block and rewrite the chainstate, all in a single write.
+    /// 
+    /// # Returns
+    /// An Result<Range<u64>, Status> which is the heights of the dropped blocks, empty if nothing was dropped, 
+    /// or an error if there is no genesis block or latest block.
+    /// 
+    pub fn recover(&mut self) -> Result<Range<u64>, Status> {
+        let mut last_good_block: Block = self.get_block(0)?;
+
+        let latest_block_height: u64 = self.get_latest_block()
+            .map_err(|e| e.annotate("Latest block"))?
+            .get_height();
+
+        while last_good_block.get_height() < latest_block_height {
+            let height: u64 = last_good_block.get_height() + 1;
+
+            match self.get_block(height) {
+                Ok(block) => last_good_block = block,
+                Err(e) => {
+                    // the latest block is updated before the block is put in add_block, so it can be missing too
+                    if e.code != rusty_leveldb::StatusCode::Corruption && e.code != rusty_leveldb::StatusCode::NotFound {
+                        return Err(e);
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        let last_good_height: u64 = last_good_block.get_height();
+        let dropped_heights: Range<u64> = last_good_height + 1..latest_block_height + 1;
+
+        if dropped_heights.is_empty() {
+            return Ok(dropped_heights);
+        }
+
+        let balances: HashMap<Point, f32> = self.replay_balances(last_good_height)?;
+
+        // one batch so that stopping in the middle of recovering leaves the db as it was
+        let mut batch: WriteBatch = WriteBatch::new();
+
+        let mut iter: DBIterator = self.db.new_iter()?;
+
+        let mut key: Vec<u8> = vec![];
+        let mut val: Vec<u8> = vec![];
+
+        while iter.advance() {
+            iter.current(&mut key, &mut val);
+
+            if key.len() >= 7 && key[0..7] == *PUBLIC_KEY_PREFIX {
+                batch.delete(&key);
+            }
+
+            // block keys are the little-endian height, every other key has a prefix and a different length
+            if key.len() == 8 {
+                let mut height_bytes: [u8; 8] = [0; 8];
+                height_bytes.copy_from_slice(&key);
+
+                if u64::from_le_bytes(height_bytes) > last_good_height {
+                    batch.delete(&key);
+                }
+            }
+        }
+
+        // put after the deletes, a batch is applied in order
+        for (public_key, balance) in balances.iter() {
+            batch.put(&BlocksDB::get_db_user_key(public_key), &balance.to_le_bytes());
+        }
+
+        batch.put(LATEST_BLOCK_KEY, &bincode::serialize(&last_good_block).unwrap());
+
+        self.db.write(batch, true)?;
+        self.db.flush()?;
+
+        Ok(dropped_heights)
+    }
+
+    /// Obtains the balance of every address once the block at a height was added, by replaying the blocks up to it from the genesis allocations.
+    /// The blocks were verified when they were added, so their transactions are only applied.
+    /// 
+    /// # Arguments
+    /// * `height` - A u64 which specifies the height of the last block to replay
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<HashMap<Point, f32>, Status> which is the balances at that height, or an error if a block could not be read.
+    /// 
+    fn replay_balances(&mut self, height: u64) -> Result<HashMap<Point, f32>, Status> {
+        let mut balances: HashMap<Point, f32> = self.genesis_allocations()?.into_iter().collect();
+
+        for replayed_height in 0..=height {
+            let block: Block = self.get_block(replayed_height)?;
+
+            for transaction in block.get_transactions() {
+                // Point::identity is miner reward
+                if transaction.get_sender() != Point::identity() {
+                    *balances.entry(transaction.get_sender()).or_insert(0.0) -= transaction.get_amount();
+                }
+
+                *balances.entry(transaction.get_recipient()).or_insert(0.0) += transaction.get_amount();
+            }
+        }
+
+        Ok(balances)
+    }
+
+    /// Writes the headers of every block in the db to a writer, for light clients that don't need the transactions.
+    /// The stream is the number of headers as a little-endian u64 followed by each bincode encoded header from genesis up.
+    /// 
+    /// # Arguments
+    /// * `w` - A &mut impl Write which specifies where to write the headers
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if all the headers were written, or an error if they were not.
+    /// 
+    pub fn export_headers(&mut self, w: &mut impl Write) -> Result<(), Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        // number of headers goes first so the reader knows when to stop
+        w.write_u64::<LittleEndian>(latest_block_height + 1)?;
+
+        for height in 0..=latest_block_height {
+            let header: BlockHeader = self.get_block(height)?.get_header();
+
+            bincode::serialize_into(&mut *w, &header).map_err(|e| 
+                Status::new(rusty_leveldb::StatusCode::IOError, &format!("{e}"))
+            )?;
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Rehashes every block of the chain after the hashing scheme or the block message changed.
+    /// Blocks are rehashed from genesis up so that every block points to the new hash of the one below it,
+    /// and each block is mined again at its own difficulty so that the chain stays valid.
+    /// 
+    /// # Modifications
+    /// This method overwrites every block and the latest block in the db.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the whole chain was rehashed, or an error if it was not.
+    /// 
+    pub fn rehash_all(&mut self) -> Result<(), Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        // genesis has no previous block
+        let mut prev_hash: String = "".to_owned();
+
+        for height in 0..=latest_block_height {
+            let mut block: Block = self.get_block(height)?;
+            block.rehash(&prev_hash);
+
+            let mut tries: u32 = 0;
+            while !block.confirm_difficulty() {
+                if tries == u32::MAX {
+                    return Err(Status::new(rusty_leveldb::StatusCode::NotSupported, 
+                        &format!("No nonce satisfies the difficulty of block at height {}", height)));
+                }
+
+                block.increment_and_hash();
+                tries += 1;
+            }
+
+            // overwrite, put_block only adds new blocks
+            self.db.put(&height.to_le_bytes(), &bincode::serialize(&block).unwrap())?;
+            prev_hash = block.get_hash();
+
+            if height == latest_block_height {
+                self.update_latest_block(&block)?;
+            }
+        }
+
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Reads back a stream of headers written by export_headers and validates their linkage and proof of work.
+    /// 
+    /// # Arguments
+    /// * `r` - A &mut impl Read which specifies where to read the headers from
+    /// 
+    /// # Returns
+    /// An Result<Vec<BlockHeader>, Status> which is the headers if they form a valid chain, or an error if they do not.
+    /// 
+    pub fn validate_headers(r: &mut impl Read) -> Result<Vec<BlockHeader>, Status> {
+        let count: u64 = r.read_u64::<LittleEndian>()?;
+
+        // same encoding as bincode::serialize_into but bounded, since the stream can come from anyone
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .with_limit(MAX_HEADER_BYTES);
+
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        for _ in 0..count {
+            let header: BlockHeader = options.deserialize_from(&mut *r).map_err(|e| 
+                Status::new(rusty_leveldb::StatusCode::Corruption, &format!("{e}"))
+            )?;
+
+            headers.push(header);
+        }
+
+        if !BlockHeader::validate_headers(&headers) {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Headers do not form a valid chain"));
+        }
+
+        Ok(headers)
+    }
+
+    /// Writes every block of the chain from genesis up as frames, each the length of the block's bytes followed by them.
+    /// 
+    /// # Arguments
+    /// * `w` - A &mut impl Write which specifies where to write the frames
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if all the blocks were written, or an error if they were not.
+    /// 
+    pub fn export_stream(&mut self, w: &mut impl Write) -> Result<(), Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        for height in 0..=latest_block_height {
+            let block_bytes: Vec<u8> = self.get_block(height)?.to_bytes();
+
+            w.write_u64::<LittleEndian>(block_bytes.len() as u64)?;
+            w.write_all(&block_bytes)?;
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Reads a chain of frames written by export_stream until the stream ends, validating each block against
+    /// the one before it as it arrives, the way ChainValidator does. Stops at the first invalid block.
+    /// The chainstate isn't rebuilt, so the transactions are only checked on their own and not against balances.
+    /// 
+    /// # Arguments
+    /// * `r` - A &mut impl Read which specifies where to read the frames from
+    /// 
+    /// # Returns
+    /// An Result<Vec<Block>, Status> which is the blocks if they form a valid chain, or an error on the first frame that does not.
+    /// 
+    pub fn import_stream(r: &mut impl Read) -> Result<Vec<Block>, Status> {
+        let mut validator: ChainValidator = ChainValidator::new();
+        let mut blocks: Vec<Block> = Vec::new();
+
+        loop {
+            let height: u64 = blocks.len() as u64;
+
+            // the stream can only end between two frames
+            let mut length_bytes: [u8; 8] = [0; 8];
+            if r.read(&mut length_bytes[..1])? == 0 {
+                break;
+            }
+            r.read_exact(&mut length_bytes[1..]).map_err(|e| Status::from(e).annotate(format!("Block at height {}", height)))?;
+
+            let block_length: u64 = u64::from_le_bytes(length_bytes);
+            if block_length > MAX_FRAME_BYTES {
+                return Err(Status::new(rusty_leveldb::StatusCode::Corruption, 
+                    &format!("Block at height {} is {} bytes, more than {}", height, block_length, MAX_FRAME_BYTES)));
+            }
+
+            let mut block_bytes: Vec<u8> = vec![0; block_length as usize];
+            r.read_exact(&mut block_bytes).map_err(|e| Status::from(e).annotate(format!("Block at height {}", height)))?;
+
+            let block: Block = Block::from_bytes(&block_bytes).map_err(|e|
+                Status::new(rusty_leveldb::StatusCode::Corruption, &format!("Block at height {}: {}", height, e))
+            )?;
+
+            validator.accept(block.clone()).map_err(|e| e.annotate(format!("Block at height {}", height)))?;
+            blocks.push(block);
+        }
+
+        if blocks.is_empty() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Chain has no genesis block"));
+        }
+
+        Ok(blocks)
+    }
+
+    /// Writes every block of the chain from genesis up as one line of base64 per block, to dump or share the chain as text.
+    /// 
+    /// # Arguments
+    /// * `w` - A &mut impl Write which specifies where to write the blocks
+    /// 
+    /// # Modifications
+    /// This method changes the internal state of the DB object by calling get on it.
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if all the blocks were written, or an error if they were not.
+    /// 
+    pub fn export_base64_lines(&mut self, w: &mut impl Write) -> Result<(), Status> {
+        let latest_block_height: u64 = self.get_latest_block()?.get_height();
+
+        for height in 0..=latest_block_height {
+            writeln!(w, "{}", self.get_block(height)?.to_base64())?;
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Reads back a chain written by export_base64_lines and validates each block in order, the way ChainValidator does.
+    /// The chainstate isn't rebuilt, so the transactions are only checked on their own and not against balances.
+    /// 
+    /// # Arguments
+    /// * `r` - A &mut impl BufRead which specifies where to read the lines from
+    /// 
+    /// # Returns
+    /// An Result<Vec<Block>, Status> which is the blocks if they form a valid chain, or an error on the first line that does not.
+    /// 
+    pub fn validate_base64_lines(r: &mut impl BufRead) -> Result<Vec<Block>, Status> {
+        let mut validator: ChainValidator = ChainValidator::new();
+        let mut blocks: Vec<Block> = Vec::new();
+
+        for (i, line) in r.lines().enumerate() {
+            let line: String = line?;
+
+            let block: Block = Block::from_base64(line.trim_end()).map_err(|e|
+                Status::new(rusty_leveldb::StatusCode::Corruption, &format!("Line {}: {}", i + 1, e))
+            )?;
+
+            validator.accept(block.clone()).map_err(|e| e.annotate(format!("Line {}", i + 1)))?;
+            blocks.push(block);
+        }
+
+        if blocks.is_empty() {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidData, "Chain has no genesis block"));
+        }
+
+        Ok(blocks)
+    }
+}
+
+/// Obtains the median of block timestamps, the upper one of the middle two when there is an even number of them.
+/// 
+/// # Arguments
+/// * `timestamps` - A Vec<u64> which specifies the timestamps, there has to be at least one
+/// 
+/// # Returns
+/// A u64 which is the median timestamp.
+/// 
+#[allow(dead_code)]
+pub(crate) fn median_timestamp(mut timestamps: Vec<u64>) -> u64 {
+    timestamps.sort();
+
+    timestamps[timestamps.len() / 2]
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ecdsa::secp256k1::{get_curve_precomputed_points, Secp256k1, W};
+    use num_bigint::BigInt;
+
+    pub(crate) fn mem_db() -> BlocksDB {
+        BlocksDB { db: DB::open("mem", rusty_leveldb::in_memory()).unwrap() }
+    }
+
+    pub(crate) fn key(n: u32) -> (Point, BigInt) {
+        let private_key: BigInt = BigInt::from(n) * BigInt::from(1234567u64) + BigInt::from(99);
+        (Secp256k1::new().g.multiply(private_key.clone(), W, get_curve_precomputed_points()).unwrap(), private_key)
+    }
+
+    pub(crate) fn next_block(db: &mut BlocksDB, transactions: &Vec<Transaction>) -> Block {
+        let mut block: Block = db.new_block(transactions).unwrap();
+        block.reward_miner(&key(9).0);
+
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+
+        block
+    }
+
+    #[test]
+    fn exported_headers_validate_on_their_own() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let mut exported: Vec<u8> = Vec::new();
+        db.export_headers(&mut exported).unwrap();
+
+        let headers: Vec<BlockHeader> = BlocksDB::validate_headers(&mut exported.as_slice()).unwrap();
+        assert_eq!(headers.len(), 4);
+        for (height, header) in headers.iter().enumerate() {
+            assert_eq!(header.get_hash(), db.get_block(height as u64).unwrap().get_hash());
+        }
+
+        // a flipped byte in the last header breaks its hash
+        let mut tampered: Vec<u8> = exported.clone();
+        let last: usize = tampered.len() - 3;
+        tampered[last] ^= 1;
+        assert!(BlocksDB::validate_headers(&mut tampered.as_slice()).is_err());
+
+        // a stream that ends before its announced number of headers
+        assert!(BlocksDB::validate_headers(&mut &exported[..exported.len() - 10]).is_err());
+    }
+
+    #[test]
+    fn base64_lines_round_trip_and_a_bad_line_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let mut exported: Vec<u8> = Vec::new();
+        db.export_base64_lines(&mut exported).unwrap();
+        assert_eq!(exported.iter().filter(|c| **c == b'\n').count(), 4);
+
+        let blocks: Vec<Block> = BlocksDB::validate_base64_lines(&mut exported.as_slice()).unwrap();
+        assert_eq!(blocks.len(), 4);
+        for (height, block) in blocks.iter().enumerate() {
+            assert_eq!(block.get_hash(), db.get_block(height as u64).unwrap().get_hash());
+        }
+
+        // a line that isn't base64 anymore
+        let text: String = String::from_utf8(exported).unwrap();
+        let mut lines: Vec<String> = text.lines().map(str::to_owned).collect();
+        lines[2].insert(0, '!');
+        let tampered: String = lines.join("\n");
+        let err: Status = BlocksDB::validate_base64_lines(&mut tampered.as_bytes()).err().unwrap();
+        assert!(err.err.contains("Line 3"));
+
+        // blocks out of order don't form a chain
+        lines[2].remove(0);
+        lines.swap(1, 2);
+        assert!(BlocksDB::validate_base64_lines(&mut lines.join("\n").as_bytes()).is_err());
+
+        assert!(BlocksDB::validate_base64_lines(&mut "".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn block_claiming_an_easier_difficulty_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 71);
+
+        let latest: Block = db.get_latest_block().unwrap();
+        let block: Block = next_block(&mut db, &vec![]);
+        assert_ne!(block.get_difficulty(), latest.get_difficulty());
+
+        // the window came too fast so the retarget should be harder, but the block kept the easiest difficulty
+        let mut easier: Block = block.clone();
+        easier.set_difficulty(latest.get_difficulty());
+        assert!(easier.confirm_hash() && easier.confirm_difficulty());
+        assert!(db.add_block(&easier).is_err());
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 71);
+
+        db.add_block(&block).unwrap();
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 72);
+    }
+
+    #[test]
+    fn difficulty_change_off_a_retarget_height_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 72);
+
+        // 73 isn't a retarget height, it has to keep the difficulty 72 retargeted to
+        let latest: Block = db.get_latest_block().unwrap();
+        let block: Block = next_block(&mut db, &vec![]);
+        assert_eq!(block.get_difficulty(), latest.get_difficulty());
+
+        // even a harder difficulty, which the block does satisfy, is refused there
+        let mut harder: Block = block.clone();
+        harder.set_difficulty(latest.get_difficulty() >> 4);
+        while !harder.confirm_difficulty() {
+            harder.increment_and_hash();
+        }
+        assert!(harder.confirm_hash());
+
+        let error: Status = db.add_block(&harder).err().unwrap();
+        assert!(error.err.contains("should be"));
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 72);
+
+        db.add_block(&block).unwrap();
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 73);
+    }
+
+
+    #[test]
+    fn corrupt_trailing_block_is_dropped_on_recovery() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..2 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        // block 3 moves balances that must be undone with it
+        let transaction: Transaction = Transaction::new(&a, &b, 4., &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+        assert_eq!(db.get_balance(&b).unwrap(), 14.);
+
+        db.db.put(&3u64.to_le_bytes(), b"garbage").unwrap();
+
+        assert_eq!(db.recover().unwrap(), 3..4);
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 2);
+        assert!(db.get_block(3).is_err());
+        assert_eq!(db.get_balance(&a).unwrap(), 10.);
+        assert_eq!(db.get_balance(&b).unwrap(), 10.);
+        let reward: f32 = Transaction::reward_transaction(&key(9).0).get_amount();
+        assert_eq!(db.get_balance(&key(9).0).unwrap(), 2. * reward);
+
+        // nothing left to drop, and the chain grows from the recovered tip
+        assert!(db.recover().unwrap().is_empty());
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 3);
+    }
+
+    #[test]
+    fn latest_block_that_was_never_put_is_dropped_on_recovery() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+
+        // stopped after updating the latest block but before putting it
+        let block: Block = next_block(&mut db, &vec![]);
+        db.update_latest_block(&block).unwrap();
+
+        assert_eq!(db.recover().unwrap(), 2..3);
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 1);
+    }
+
+    #[test]
+    fn rebuilt_chainstate_starts_from_the_genesis_allocations() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let transaction: Transaction = Transaction::new(&a, &b, 4., &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+
+        db.rebuild_chainstate().unwrap();
+        assert_eq!(db.get_balance(&a).unwrap(), 6.);
+        assert_eq!(db.get_balance(&b).unwrap(), 14.);
+    }
+
+    // blocks have no timestamp setter, the timestamp comes right after the height and the hash in the serialized block
+    fn with_timestamp(block: &Block, timestamp: u64) -> Block {
+        let mut bytes: Vec<u8> = bincode::serialize(block).unwrap();
+        let offset: usize = 16 + block.get_hash().len();
+        bytes[offset..offset + 8].copy_from_slice(&timestamp.to_le_bytes());
+
+        bincode::deserialize(&bytes).unwrap()
+    }
+
+    /// puts blocks at the genesis difficulty up to a height below the first retarget, without mining them.
+    /// they come every 300 seconds, 4 times faster than the 1200 they should take
+    pub(crate) fn put_fast_blocks(db: &mut BlocksDB, up_to: u64) {
+        let genesis: Block = db.get_block(0).unwrap();
+        let mut prev_block: Block = db.get_latest_block().unwrap();
+
+        for height in prev_block.get_height() + 1..=up_to {
+            let mut block: Block = Block::new(&prev_block, &genesis, &vec![]).unwrap();
+            block.reward_miner(&key(9).0);
+            let mut block: Block = with_timestamp(&block, genesis.get_timestamp() + height * 300);
+            block.rehash(&prev_block.get_hash());
+            db.put_block(&block).unwrap();
+            db.update_latest_block(&block).unwrap();
+            prev_block = block;
+        }
+    }
+
+    #[test]
+    fn chain_age_and_average_interval_come_from_the_timestamps() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let genesis: Block = db.get_block(0).unwrap();
+        assert_eq!(db.chain_age_seconds().unwrap(), 0);
+        assert_eq!(db.average_block_interval().unwrap(), 0.0);
+
+        let mut prev_block: Block = genesis.clone();
+        for offset in [10, 25, 60] {
+            let block: Block = with_timestamp(&Block::new(&prev_block, &genesis, &vec![]).unwrap(), genesis.get_timestamp() + offset);
+            assert_eq!(block.get_timestamp(), genesis.get_timestamp() + offset);
+
+            db.put_block(&block).unwrap();
+            db.update_latest_block(&block).unwrap();
+            prev_block = block;
+        }
+
+        assert_eq!(db.chain_age_seconds().unwrap(), 60);
+        assert_eq!(db.average_block_interval().unwrap(), 20.0);
+    }
+
+    #[test]
+    fn sync_progress_is_the_share_of_the_best_known_height() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        assert_eq!(db.sync_progress(4).unwrap(), 0.0);
+
+        for _ in 0..2 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        assert_eq!(db.sync_progress(4).unwrap(), 0.5);
+        assert_eq!(db.sync_progress(2).unwrap(), 1.0);
+
+        // a peer behind the node doesn't make the progress go over 1
+        assert_eq!(db.sync_progress(1).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn valid_chain_with_another_tip_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..2 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let tip: String = db.get_latest_block().unwrap().get_hash();
+        let below_tip: String = db.get_block(1).unwrap().get_hash();
+
+        assert!(db.validate_chain().is_ok());
+        assert!(db.validate_against_tip(&tip));
+        assert!(!db.validate_against_tip(&below_tip));
+    }
+
+    #[test]
+    fn known_addresses_include_senders_recipients_and_miners() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        let (c, _) = key(3);
+        db.init_db(&a, &b);
+
+        let transaction: Transaction = Transaction::new(&a, &c, 1., &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+
+        let expected: HashSet<Point> = [a, b, c, key(9).0].into_iter().collect();
+        assert!(db.known_addresses().unwrap() == expected);
+    }
+
+    #[test]
+    fn verify_dir_reports_a_tampered_block() {
+        let dir = std::env::temp_dir().join("snailnote_verify_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        {
+            let mut db: BlocksDB = BlocksDB { db: DB::open(&dir, Options::default()).unwrap() };
+            let (a, _) = key(1);
+            let (b, _) = key(2);
+            db.init_db(&a, &b);
+
+            for _ in 0..3 {
+                let block: Block = next_block(&mut db, &vec![]);
+                db.add_block(&block).unwrap();
+            }
+
+            assert!(db.validate_chain().is_ok());
+
+            // the timestamp is changed without rehashing the block
+            let block: Block = db.get_block(2).unwrap();
+            db.db.put(&2u64.to_le_bytes(), &bincode::serialize(&with_timestamp(&block, block.get_timestamp() + 1)).unwrap()).unwrap();
+            db.db.flush().unwrap();
+        }
+
+        let issues: Vec<Status> = BlocksDB::verify_dir(&dir).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(issues.iter().any(|issue| 
+            issue.err.contains("Block at height 2") && issue.err.contains("Block hash does not match block data")
+        ));
+        assert!(!issues.iter().any(|issue| issue.err.contains("Block at height 1")));
+    }
+
+    #[test]
+    fn reward_spent_in_its_own_block_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        let (miner, private_miner) = key(9);
+        db.init_db(&a, &b);
+
+        // the reward comes first so the miner has the balance for the spend when it's verified
+        let reward: Transaction = Transaction::reward_transaction(&miner);
+        let spend: Transaction = Transaction::new(&miner, &a, 1., &private_miner);
+        let block: Block = next_block(&mut db, &vec![reward, spend]);
+
+        let error: Status = db.add_block(&block).err().unwrap();
+        assert!(error.err.contains("spends its reward in the same block"));
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 0);
+        assert_eq!(db.get_balance(&a).unwrap(), 10.);
+    }
+
+    #[test]
+    fn valid_prefix_stops_at_the_first_invalid_block() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..5 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let mut blocks: Vec<Block> = (0..=5).map(|height| db.get_block(height).unwrap()).collect();
+        assert_eq!(BlocksDB::valid_prefix_length(&blocks), 6);
+
+        // block 4 no longer matches its hash, blocks 0 to 3 are still valid
+        blocks[4] = with_timestamp(&blocks[4], blocks[4].get_timestamp() + 1);
+        assert_eq!(BlocksDB::valid_prefix_length(&blocks), 4);
+    }
+
+    #[test]
+    fn rehashed_chain_is_valid_again() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let transaction: Transaction = Transaction::new(&a, &b, 1., &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+
+        // every stored hash above genesis now disagrees with its block's message, as if the scheme had changed.
+        // genesis is fixed, a changed one would be another network's
+        for height in 1..=2u64 {
+            let block: Block = db.get_block(height).unwrap();
+            let changed: Block = with_timestamp(&block, block.get_timestamp() + 1);
+            db.db.put(&height.to_le_bytes(), &bincode::serialize(&changed).unwrap()).unwrap();
+
+            if height == 2 {
+                db.update_latest_block(&changed).unwrap();
+            }
+        }
+        assert!(db.validate_chain().is_err());
+
+        db.rehash_all().unwrap();
+        assert!(db.validate_chain().is_ok());
+        assert_eq!(db.get_latest_block().unwrap().get_hash(), db.get_block(2).unwrap().get_hash());
+    }
+
+    /// stores a block in place of the one at its height without verifying it
+    pub(crate) fn overwrite_block(db: &mut BlocksDB, block: &Block) {
+        db.db.put(&block.get_height().to_le_bytes(), &bincode::serialize(block).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn validate_chain_catches_a_swapped_transaction_and_a_changed_link() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![Transaction::new(&a, &b, 1., &private_a)]);
+        let other: Block = next_block(&mut db, &vec![Transaction::new(&a, &b, 2., &private_a)]);
+        db.add_block(&block).unwrap();
+        for _ in 0..2 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+        assert!(db.validate_chain().is_ok());
+
+        // the other block's transactions are valid, but the hash only covers the merkel root
+        let mut value: serde_json::Value = serde_json::to_value(&block).unwrap();
+        value["transactions"] = serde_json::to_value(&other).unwrap()["transactions"].take();
+        let swapped: Block = serde_json::from_value(value).unwrap();
+        assert!(swapped.confirm_hash() && swapped.confirm_difficulty() && swapped.confirm_transactions());
+
+        overwrite_block(&mut db, &swapped);
+        let error: Status = db.validate_chain().err().unwrap();
+        assert!(error.err.contains("Block at height 1") && error.err.contains("merkel root does not match"));
+        overwrite_block(&mut db, &block);
+        assert!(db.validate_chain().is_ok());
+
+        // block 2 is mined again on top of something else than block 1
+        let mut relinked: Block = db.get_block(2).unwrap();
+        relinked.rehash(&db.get_block(0).unwrap().get_hash());
+        while !relinked.confirm_difficulty() {
+            relinked.increment_and_hash();
+        }
+
+        overwrite_block(&mut db, &relinked);
+        let error: Status = db.validate_chain().err().unwrap();
+        assert!(error.err.contains("Block at height 2") && error.err.contains("does not link"));
+    }
+
+    #[test]
+    fn block_range_is_clamped_to_the_chain() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let heights = |blocks: Vec<Block>| -> Vec<u64> { blocks.iter().map(|block| block.get_height()).collect() };
+
+        assert_eq!(heights(db.get_block_range(1, 3).unwrap()), vec![1, 2]);
+        assert_eq!(heights(db.get_block_range(2, 10).unwrap()), vec![2, 3]);
+        assert!(db.get_block_range(5, 10).unwrap().is_empty());
+        assert!(db.get_block_range(3, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn transactions_added_since_a_snapshot() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+        db.init_db(&a, &b);
+
+        let before: Transaction = Transaction::new(&a, &b, 1., &private_a);
+        let block: Block = next_block(&mut db, &vec![before]);
+        db.add_block(&block).unwrap();
+
+        let snapshot: u64 = db.get_latest_block().unwrap().get_height();
+
+        let first: Transaction = Transaction::new(&a, &b, 2., &private_a);
+        let second: Transaction = Transaction::new(&b, &a, 3., &private_b);
+        for transaction in [first.clone(), second.clone()] {
+            let block: Block = next_block(&mut db, &vec![transaction]);
+            db.add_block(&block).unwrap();
+        }
+
+        let added: Vec<(u64, String)> = db.transactions_added_since(snapshot).unwrap().iter()
+            .map(|(height, transaction)| (*height, transaction.get_hash()))
+            .collect();
+
+        // each block also has its reward first
+        let rewards: Vec<Transaction> = (2..=3).map(|height| db.get_block(height).unwrap().get_reward_transaction().unwrap()).collect();
+        assert_eq!(added, vec![
+            (2, rewards[0].get_hash()), (2, first.get_hash()),
+            (3, rewards[1].get_hash()), (3, second.get_hash())
+        ]);
+
+        assert!(db.transactions_added_since(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn block_pointing_two_blocks_back_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..2 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        // right height for the tip, but linked to the block below the tip
+        let mut block: Block = next_block(&mut db, &vec![]);
+        block.rehash(&db.get_block(1).unwrap().get_hash());
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+
+        assert!(db.add_block(&block).is_err());
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 2);
+    }
+
+    #[test]
+    fn difficulty_change_is_the_percent_change_in_expected_attempts() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        // 256 expected attempts, then the lowest chunk halves its passing values, then back again
+        for (height, difficulty) in [(1u64, 0x77777777u32), (2, 0x77777773), (3, 0x77777777)] {
+            let mut block: Block = db.get_block(height).unwrap();
+            block.set_difficulty(difficulty);
+            db.db.put(&height.to_le_bytes(), &bincode::serialize(&block).unwrap()).unwrap();
+        }
+
+        assert_eq!(db.difficulty_change_at(2).unwrap(), Some(100.0));
+        assert_eq!(db.difficulty_change_at(3).unwrap(), Some(-50.0));
+
+        assert_eq!(db.difficulty_change_at(0).unwrap(), None);
+        assert_eq!(db.difficulty_change_at(4).unwrap(), None);
+    }
+
+    #[test]
+    fn average_difficulty_is_the_mean_of_the_expected_attempts() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        assert_eq!(db.average_difficulty().unwrap(), 1.0);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        // 256, 512 and 256 expected attempts on top of genesis' single one
+        for (height, difficulty) in [(1u64, 0x77777777u32), (2, 0x77777773), (3, 0x77777777)] {
+            let mut block: Block = db.get_block(height).unwrap();
+            block.set_difficulty(difficulty);
+            overwrite_block(&mut db, &block);
+        }
+
+        assert_eq!(db.average_difficulty().unwrap(), (1.0 + 256.0 + 512.0 + 256.0) / 4.0);
+    }
+
+    #[test]
+    fn effective_time_is_the_median_of_the_blocks_before() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let genesis: Block = db.get_block(0).unwrap();
+        let mut prev_block: Block = genesis.clone();
+        for height in 1..=14 {
+            let block: Block = with_timestamp(&Block::new(&prev_block, &genesis, &vec![]).unwrap(), genesis.get_timestamp() + height * 60);
+            db.put_block(&block).unwrap();
+            db.update_latest_block(&block).unwrap();
+            prev_block = block;
+        }
+
+        assert_eq!(db.effective_time(0).unwrap(), Some(genesis.get_timestamp()));
+        assert_eq!(db.effective_time(1).unwrap(), Some(genesis.get_timestamp()));
+
+        // blocks 3 to 13, the block at 14 itself isn't counted
+        assert_eq!(db.effective_time(14).unwrap(), Some(genesis.get_timestamp() + 8 * 60));
+        assert_eq!(db.effective_time(15).unwrap(), None);
+    }
+
+    #[test]
+    fn genesis_claiming_another_difficulty_is_invalid() {
+        let genesis: Block = Block::new_genesis();
+        assert_eq!(genesis.get_difficulty(), GENESIS_DIFFICULTY);
+        assert!(BlocksDB::verify_genesis(&genesis).is_ok());
+
+        // the hash still matches the block, only the difficulty is off
+        let mut harder: Block = genesis.clone();
+        harder.set_difficulty(0x7fffffff);
+        assert!(harder.confirm_hash());
+        assert!(BlocksDB::verify_genesis(&harder).is_err());
+    }
+
+    #[test]
+    fn next_difficulty_only_changes_at_a_retarget_height() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        put_fast_blocks(&mut db, 70);
+        assert_eq!(db.calculate_next_difficulty().unwrap(), GENESIS_DIFFICULTY);
+        put_fast_blocks(&mut db, 71);
+
+        // a quarter of the passing values
+        assert_eq!(db.calculate_next_difficulty().unwrap(), 0xfffffff3);
+        assert_eq!(db.new_block(&vec![]).unwrap().get_difficulty(), 0xfffffff3);
+    }
+
+    /// mines a block again at the difficulty it claims
+    fn mined(mut block: Block) -> Block {
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+
+        block
+    }
+
+    #[test]
+    fn boundary_block_is_retargeted_and_the_block_after_keeps_it() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 71);
+
+        // the boundary block at 72 can't keep the difficulty of the block before it
+        let boundary: Block = next_block(&mut db, &vec![]);
+        assert_eq!(boundary.get_difficulty(), 0xfffffff3);
+        db.add_block(&boundary).unwrap();
+
+        // the block after the boundary can't retarget again
+        let after: Block = next_block(&mut db, &vec![]);
+        assert_eq!(after.get_difficulty(), boundary.get_difficulty());
+
+        let mut retargeted_again: Block = after.clone();
+        retargeted_again.set_difficulty(0xfffffff0);
+        assert!(db.add_block(&mined(retargeted_again)).is_err());
+
+        db.add_block(&after).unwrap();
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 73);
+    }
+
+    #[test]
+    fn boundary_block_uses_the_first_and_last_timestamps_of_the_window() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 70);
+
+        // every block but the last of the window came fast, the window as a whole still took 71 * 1200 seconds
+        let genesis: Block = db.get_block(0).unwrap();
+        let last: Block = with_timestamp(&db.new_block(&vec![]).unwrap(), genesis.get_timestamp() + 71 * 1200);
+        db.put_block(&last).unwrap();
+        db.update_latest_block(&last).unwrap();
+
+        assert_eq!(db.calculate_next_difficulty().unwrap(), GENESIS_DIFFICULTY);
+
+        let boundary: Block = next_block(&mut db, &vec![]);
+        let mut harder: Block = boundary.clone();
+        harder.set_difficulty(0xfffffff3);
+        assert!(db.add_block(&mined(harder)).is_err());
+
+        db.add_block(&boundary).unwrap();
+        assert_eq!(db.get_latest_block().unwrap().get_difficulty(), GENESIS_DIFFICULTY);
+    }
+
+    #[test]
+    fn next_difficulty_is_reported_across_a_retarget_boundary() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 71);
+
+        // the tip still has the genesis difficulty, the next block is the boundary block
+        assert_eq!(db.get_latest_block().unwrap().get_difficulty(), GENESIS_DIFFICULTY);
+        assert_eq!(db.calculate_next_difficulty().unwrap(), 0xfffffff3);
+
+        let boundary: Block = mined(db.new_block(&vec![Transaction::reward_transaction(&key(9).0)]).unwrap());
+        db.add_block(&boundary).unwrap();
+
+        // past the boundary the next block keeps the retargeted difficulty
+        assert_eq!(db.calculate_next_difficulty().unwrap(), 0xfffffff3);
+        assert_eq!(db.new_block(&vec![]).unwrap().get_difficulty(), 0xfffffff3);
+    }
+
+    #[test]
+    fn add_block_rejects_a_block_of_another_network() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        // every hash satisfies the difficulty this close to genesis, only the magic is wrong
+        let mut block: Block = next_block(&mut db, &vec![]);
+        block.set_hash_for_network(0x74657374);
+        assert!(block.confirm_difficulty());
+        assert!(db.add_block(&block).is_err());
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 0);
+    }
+
+    #[test]
+    fn median_fee_rate_is_taken_over_the_window() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        assert_eq!(db.median_fee_rate(10).unwrap(), 0.0);
+
+        let old: Transaction = Transaction::new_with_fee(&a, &b, 1.0, 0.9, &private_a);
+        let low: Transaction = Transaction::new_with_fee(&a, &b, 1.0, 0.1, &private_a);
+        let high: Transaction = Transaction::new_with_fee(&a, &b, 1.0, 0.4, &private_a);
+        let middle: Transaction = Transaction::new_with_fee(&a, &b, 1.0, 0.2, &private_a);
+
+        for transactions in [vec![old.clone()], vec![low, high.clone()], vec![middle.clone()], vec![]] {
+            let block: Block = next_block(&mut db, &transactions);
+            db.add_block(&block).unwrap();
+        }
+
+        let fee_rate = |transaction: &Transaction| -> f64 { transaction.get_fee() as f64 / bincode::serialized_size(transaction).unwrap() as f64 };
+
+        // the last 3 blocks have 3 transactions other than their rewards, the empty block adds none
+        assert_eq!(db.median_fee_rate(3).unwrap(), fee_rate(&middle));
+
+        // with the oldest block there are 4, so the median is between the two middle ones
+        assert_eq!(db.median_fee_rate(4).unwrap(), (fee_rate(&middle) + fee_rate(&high)) / 2.0);
+        assert_eq!(db.median_fee_rate(100).unwrap(), db.median_fee_rate(4).unwrap());
+
+        assert_eq!(db.median_fee_rate(1).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn submit_solution_stamps_the_nonce_and_tag() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        // the window came too fast, so the next block needs a few attempts
+        put_fast_blocks(&mut db, 71);
+
+        let mut template: Block = db.new_block(&vec![Transaction::new(&a, &b, 1.0, &private_a)]).unwrap();
+        template.reward_miner(&key(9).0);
+        assert_ne!(template.get_difficulty(), GENESIS_DIFFICULTY);
+
+        let mut solved: Block = template.clone();
+        solved.set_reward_tag(b"pool").unwrap();
+        while !solved.confirm_difficulty() {
+            solved.increment_and_hash();
+        }
+
+        let mut bad_nonce: u32 = 0;
+        loop {
+            let mut attempt: Block = solved.clone();
+            attempt.set_nonce(bad_nonce);
+
+            if !attempt.confirm_difficulty() {
+                break;
+            }
+
+            bad_nonce += 1;
+        }
+
+        let tip_hash: String = db.get_latest_block().unwrap().get_hash();
+        assert!(db.submit_solution(template.clone(), bad_nonce, b"pool").is_err());
+        assert_eq!(db.get_latest_block().unwrap().get_hash(), tip_hash);
+        assert_eq!(db.get_balance(&a).unwrap(), 10.0);
+
+        // a template without a reward has nothing to put the tag on
+        let unrewarded: Block = db.new_block(&vec![]).unwrap();
+        assert!(db.submit_solution(unrewarded, solved.get_nonce(), b"pool").is_err());
+
+        let added: Block = db.submit_solution(template.clone(), solved.get_nonce(), b"pool").unwrap();
+        assert_eq!(added.get_hash(), solved.get_hash());
+        assert_eq!(db.get_latest_block().unwrap().get_hash(), solved.get_hash());
+        assert_eq!(db.get_latest_block().unwrap().get_reward_transaction().unwrap().get_tag(), b"pool".to_vec());
+        assert_eq!(db.get_balance(&a).unwrap(), 9.0);
+    }
+
+    #[test]
+    fn hash_meets_target_of_the_next_block() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let easy_hash: String = format!("{}00000000", "f".repeat(56));
+        let hard_hash: String = "f".repeat(64);
+
+        // every hash satisfies the genesis difficulty
+        assert!(db.hash_meets_target(&hard_hash).unwrap());
+
+        put_fast_blocks(&mut db, 71);
+        assert_ne!(db.calculate_next_difficulty().unwrap(), GENESIS_DIFFICULTY);
+        assert!(db.hash_meets_target(&easy_hash).unwrap());
+        assert!(!db.hash_meets_target(&hard_hash).unwrap());
+        assert!(!db.hash_meets_target("not a hash").unwrap());
+    }
+
+    #[test]
+    fn competing_block_at_the_tip_is_an_observed_fork() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![]);
+        let mut competing: Block = block.clone();
+        competing.set_reward_tag(b"other miner").unwrap();
+        db.add_block(&block).unwrap();
+
+        assert!(db.add_block(&competing).is_err());
+        assert_eq!(db.observed_forks().unwrap(), vec![(1, competing.get_hash())]);
+        assert_eq!(db.get_latest_block().unwrap().get_hash(), block.get_hash());
+
+        // the chain's own block and a block that doesn't link to the chain aren't forks
+        assert!(db.add_block(&block).is_err());
+        let mut unlinked: Block = competing.clone();
+        unlinked.rehash(&"0".repeat(64));
+        assert!(db.add_block(&unlinked).is_err());
+        assert_eq!(db.observed_forks().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn observed_forks_are_capped_from_the_lowest_height() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let first: Block = next_block(&mut db, &vec![]);
+        let mut low_fork: Block = first.clone();
+        low_fork.set_reward_tag(b"low").unwrap();
+        db.add_block(&first).unwrap();
+        let second: Block = next_block(&mut db, &vec![]);
+        db.add_block(&second).unwrap();
+
+        assert!(db.add_block(&low_fork).is_err());
+        for i in 0..MAX_OBSERVED_FORKS as u64 {
+            let mut fork: Block = second.clone();
+            fork.set_reward_tag(&i.to_le_bytes()).unwrap();
+            assert!(db.add_block(&fork).is_err());
+        }
+
+        let forks: Vec<(u64, String)> = db.observed_forks().unwrap();
+        assert_eq!(forks.len(), MAX_OBSERVED_FORKS);
+        assert!(forks.iter().all(|(height, _)| *height == 2));
+
+        // a new fork below all of the kept ones is dropped right away
+        let mut lower_fork: Block = first.clone();
+        lower_fork.set_reward_tag(b"lower").unwrap();
+        assert!(db.add_block(&lower_fork).is_err());
+        assert_eq!(db.observed_forks().unwrap(), forks);
+    }
+
+    #[test]
+    fn confirmation_depth_as_of_a_height() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+
+        let transaction: Transaction = Transaction::new(&a, &b, 1., &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction.clone()]);
+        db.add_block(&block).unwrap();
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let hash: String = transaction.get_hash();
+        assert_eq!(db.confirmation_depth_at(&hash, 2).unwrap(), Some(1));
+        assert_eq!(db.confirmation_depth_at(&hash, 4).unwrap(), Some(3));
+        assert_eq!(db.confirmation_depth_at(&hash, 5).unwrap(), Some(4));
+
+        // not in the chain yet at height 1, and height 6 isn't in the chain at all
+        assert_eq!(db.confirmation_depth_at(&hash, 1).unwrap(), None);
+        assert_eq!(db.confirmation_depth_at(&hash, 6).unwrap(), None);
+    }
+
+    #[test]
+    fn total_serialized_size_is_the_length_of_every_block_as_bytes() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let transaction: Transaction = Transaction::new(&a, &b, 1., &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+
+        let bytes: Vec<u8> = db.get_block_range(0, 3).unwrap().iter()
+            .flat_map(|block| block.to_bytes())
+            .collect();
+        assert_eq!(db.total_serialized_size().unwrap(), bytes.len());
+    }
+
+    #[test]
+    fn empty_block_ratio_counts_every_block() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        assert_eq!(db.empty_block_ratio().unwrap(), 0.0);
+
+        for transactions in [vec![], vec![Transaction::new(&a, &b, 1.0, &private_a)], vec![]] {
+            let block: Block = next_block(&mut db, &transactions);
+            db.add_block(&block).unwrap();
+        }
+
+        // 2 of the 4 blocks have only their reward
+        assert_eq!(db.empty_block_ratio().unwrap(), 0.5);
+    }
+
+    #[test]
+    fn work_weighted_block_time_weighs_the_harder_blocks() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        assert_eq!(db.work_weighted_block_time().unwrap(), 0.0);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        // the 300 second block needs 256 expected attempts, the two 100 second ones only 1
+        let genesis_timestamp: u64 = db.get_block(0).unwrap().get_timestamp();
+        for (height, offset, difficulty) in [(1u64, 100u64, 0xffffffffu32), (2, 400, 0x77777777), (3, 500, 0xffffffff)] {
+            let mut block: Block = with_timestamp(&db.get_block(height).unwrap(), genesis_timestamp + offset);
+            block.set_difficulty(difficulty);
+            overwrite_block(&mut db, &block);
+        }
+        let latest: Block = db.get_block(3).unwrap();
+        db.update_latest_block(&latest).unwrap();
+
+        assert_eq!(db.average_block_interval().unwrap(), 500.0 / 3.0);
+        assert_eq!(db.work_weighted_block_time().unwrap(), (100.0 + 300.0 * 256.0 + 100.0) / 258.0);
+        assert!(db.work_weighted_block_time().unwrap() > db.average_block_interval().unwrap());
+    }
+
+    #[test]
+    fn block_claiming_the_genesis_hash_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..4 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let genesis: Block = db.get_block(0).unwrap();
+        let mut value: serde_json::Value = serde_json::to_value(next_block(&mut db, &vec![])).unwrap();
+        value["hash"] = genesis.get_hash().into();
+        let claiming: Block = serde_json::from_value(value).unwrap();
+        assert_eq!(claiming.get_height(), 5);
+
+        let error: Status = db.add_block(&claiming).err().unwrap();
+        assert!(error.err.contains("claims the genesis hash"));
+
+        // and genesis itself can't be added a second time
+        assert!(db.add_block(&genesis).is_err());
+        assert!(db.add_block(&Block::new_genesis()).is_err());
+        assert_eq!(db.get_latest_block().unwrap().get_height(), 4);
+    }
+
+    #[test]
+    fn block_reward_is_the_reward_plus_the_fees() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+        db.init_db(&a, &b);
+
+        let transactions: Vec<Transaction> = vec![
+            Transaction::new_with_fee(&a, &b, 1.0, 0.25, &private_a),
+            Transaction::new_with_fee(&b, &a, 2.0, 0.5, &private_b)
+        ];
+        let block: Block = next_block(&mut db, &transactions);
+        db.add_block(&block).unwrap();
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+
+        let reward: f32 = Transaction::reward_transaction(&key(9).0).get_amount();
+        assert_eq!(db.block_reward(1).unwrap(), Some(reward + 0.75));
+        assert_eq!(db.block_reward(2).unwrap(), Some(reward));
+
+        // genesis has no reward and height 3 isn't in the chain yet
+        assert_eq!(db.block_reward(0).unwrap(), None);
+        assert_eq!(db.block_reward(3).unwrap(), None);
+    }
+
+    #[test]
+    fn claimed_total_work_has_to_match_the_chain() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..2 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let total_work: u128 = db.handshake_info().unwrap().get_total_work();
+        assert!(db.verify_total_work(total_work).unwrap());
+
+        // a peer claiming more or less work than its blocks have
+        assert!(!db.verify_total_work(total_work + 1).unwrap());
+        assert!(!db.verify_total_work(total_work - 1).unwrap());
+    }
+
+    #[test]
+    fn chain_fingerprint_changes_with_any_block() {
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        let mut db: BlocksDB = mem_db();
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 3);
+
+        // the same blocks give the same fingerprint
+        let mut same: BlocksDB = mem_db();
+        same.init_db(&a, &b);
+        for height in 1..=3 {
+            same.put_block(&db.get_block(height).unwrap()).unwrap();
+        }
+        same.update_latest_block(&db.get_block(3).unwrap()).unwrap();
+        let fingerprint: String = db.chain_fingerprint().unwrap();
+        assert_eq!(same.chain_fingerprint().unwrap(), fingerprint);
+
+        // one more block
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+        assert_ne!(db.chain_fingerprint().unwrap(), fingerprint);
+
+        // a different block in the middle
+        let mut middle: Block = same.get_block(2).unwrap();
+        middle.increment_and_hash();
+        overwrite_block(&mut same, &middle);
+        assert_ne!(same.chain_fingerprint().unwrap(), fingerprint);
+    }
+
+    #[test]
+    fn difficulty_series_has_every_height_in_order() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        assert_eq!(db.difficulty_series().unwrap(), vec![(0, GENESIS_DIFFICULTY)]);
+
+        // the fast window retargets block 72 to a harder difficulty
+        put_fast_blocks(&mut db, 72);
+        let series: Vec<(u64, u32)> = db.difficulty_series().unwrap();
+        assert_eq!(series.len(), 73);
+
+        for (i, (height, difficulty)) in series.iter().enumerate() {
+            assert_eq!(*height, i as u64);
+            assert_eq!(*difficulty, db.get_block(*height).unwrap().get_difficulty());
+        }
+        assert_eq!(series[71].1, GENESIS_DIFFICULTY);
+        assert!(series[72].1 < GENESIS_DIFFICULTY);
+    }
+
+    #[test]
+    fn transactions_are_verified_against_the_balances_at_a_height() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![Transaction::new(&a, &b, 4., &private_a)]);
+        db.add_block(&block).unwrap();
+        let block: Block = next_block(&mut db, &vec![Transaction::new(&b, &a, 12., &private_b)]);
+        db.add_block(&block).unwrap();
+
+        // the seeded balances aren't lost, and the miner had nothing before its first reward
+        let reward: f32 = Transaction::reward_transaction(&key(9).0).get_amount();
+        let genesis_balances: HashMap<Point, f32> = db.balances_at(0).unwrap();
+        assert_eq!(genesis_balances[&a], 10.);
+        assert_eq!(genesis_balances[&b], 10.);
+        assert_eq!(genesis_balances[&key(9).0], 0.);
+
+        let balances: HashMap<Point, f32> = db.balances_at(1).unwrap();
+        assert_eq!(balances[&a], 6.);
+        assert_eq!(balances[&b], 14.);
+        assert_eq!(balances[&key(9).0], reward);
+
+        assert_eq!(db.balances_at(2).unwrap()[&a], db.get_balance(&a).unwrap());
+        assert!(db.balances_at(3).is_err());
+
+        // b could only pay 12 once block 1 gave it 4
+        let transactions: Vec<Transaction> = vec![Transaction::new(&b, &a, 12., &private_b)];
+        assert!(db.verify_transactions_at(&transactions, 0).is_err());
+        assert_eq!(db.verify_transactions_at(&transactions, 1).unwrap()[&b], 2.);
+    }
+
+    #[test]
+    fn block_on_top_of_itself_is_rejected() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![]);
+        let mut value: serde_json::Value = serde_json::to_value(&block).unwrap();
+        value["prev_hash"] = block.get_hash().into();
+        let looping: Block = serde_json::from_value(value).unwrap();
+
+        let error: Status = BlocksDB::verify_block_with_difficulty(&looping, looping.get_difficulty()).err().unwrap();
+        assert!(error.err.contains("its own hash"));
+        assert!(db.add_block(&looping).is_err());
+
+        assert!(BlocksDB::verify_block_with_difficulty(&block, block.get_difficulty()).is_ok());
+    }
+
+    /// a clock stopped at a given time
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn tip_is_stale_after_three_block_speeds() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let block: Block = next_block(&mut db, &vec![]);
+        db.add_block(&block).unwrap();
+        let tip: u64 = block.get_timestamp();
+
+        assert_eq!(db.seconds_since_tip(&FixedClock(tip + 100)).unwrap(), 100);
+        assert!(!db.is_stale(&FixedClock(tip + 100)).unwrap());
+
+        assert!(!db.is_stale(&FixedClock(tip + 3 * BLOCK_SPEED)).unwrap());
+        assert!(db.is_stale(&FixedClock(tip + 3 * BLOCK_SPEED + 1)).unwrap());
+
+        // a clock behind the tip isn't a negative age
+        assert_eq!(db.seconds_since_tip(&FixedClock(tip - 10)).unwrap(), 0);
+        assert!(!db.is_stale(&rblock::SystemClock).unwrap());
+    }
+
+    #[test]
+    fn miner_work_share_weighs_the_harder_blocks() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        let (c, _) = key(3);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 71);
+
+        // block 72 retargets to a harder difficulty and is mined by c
+        let mut block: Block = db.new_block(&vec![]).unwrap();
+        block.reward_miner(&c);
+        while !block.confirm_difficulty() {
+            block.increment_and_hash();
+        }
+        db.add_block(&block).unwrap();
+
+        let easy_attempts: u128 = db.get_block(1).unwrap().expected_attempts();
+        let total_attempts: u128 = 72 * easy_attempts + block.expected_attempts();
+        assert!(block.expected_attempts() > easy_attempts);
+
+        // genesis counts in the total but nobody mined it
+        let share: f64 = db.miner_work_share(&c).unwrap();
+        assert!((share - block.expected_attempts() as f64 / total_attempts as f64).abs() < 1e-9);
+        assert!(share > 1.0 / 73.0);
+
+        let share: f64 = db.miner_work_share(&key(9).0).unwrap();
+        assert!((share - (71 * easy_attempts) as f64 / total_attempts as f64).abs() < 1e-9);
+
+        assert_eq!(db.miner_work_share(&a).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn framed_stream_round_trips_and_a_bad_frame_is_rejected_mid_stream() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..3 {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        let mut exported: Vec<u8> = Vec::new();
+        db.export_stream(&mut exported).unwrap();
+
+        let blocks: Vec<Block> = BlocksDB::import_stream(&mut exported.as_slice()).unwrap();
+        assert_eq!(blocks.len(), 4);
+        for (height, block) in blocks.iter().enumerate() {
+            assert_eq!(block.get_hash(), db.get_block(height as u64).unwrap().get_hash());
+        }
+
+        // the frames of genesis and block 1, then block 2 with a flipped byte
+        let frame_end = |frames: usize| -> usize {
+            let mut end: usize = 0;
+            for _ in 0..frames {
+                end += 8 + u64::from_le_bytes(exported[end..end + 8].try_into().unwrap()) as usize;
+            }
+            end
+        };
+        let mut tampered: Vec<u8> = exported.clone();
+        tampered[frame_end(2) + 20] ^= 1;
+        let error: Status = BlocksDB::import_stream(&mut tampered.as_slice()).err().unwrap();
+        assert!(error.err.contains("Block at height 2"));
+
+        // cut off in the middle of the last frame, and in the middle of its length
+        let error: Status = BlocksDB::import_stream(&mut &exported[..exported.len() - 10]).err().unwrap();
+        assert!(error.err.contains("Block at height 3"));
+        let error: Status = BlocksDB::import_stream(&mut &exported[..frame_end(3) + 4]).err().unwrap();
+        assert!(error.err.contains("Block at height 3"));
+
+        // a frame claiming more bytes than a block can have
+        let mut oversized: Vec<u8> = exported[..frame_end(1)].to_vec();
+        oversized.extend_from_slice(&(MAX_FRAME_BYTES + 1).to_le_bytes());
+        let error: Status = BlocksDB::import_stream(&mut oversized.as_slice()).err().unwrap();
+        assert!(error.err.contains("more than"));
+
+        // ending cleanly between two frames is a shorter chain, and no frame at all is no chain
+        assert_eq!(BlocksDB::import_stream(&mut &exported[..frame_end(2)]).unwrap().len(), 2);
+        assert!(BlocksDB::import_stream(&mut &exported[..0]).is_err());
+    }
+
+    #[test]
+    fn initial_chainstate_has_to_be_the_allocations() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        let transaction: Transaction = Transaction::new(&a, &b, 3.0, &private_a);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+
+        assert!(db.verify_initial_chainstate(&[(a.clone(), 10.0), (b.clone(), 10.0)]).is_ok());
+        assert!(db.verify_initial_chainstate(&[(a.clone(), 10.0), (b.clone(), 20.0)]).is_err());
+        assert!(db.verify_initial_chainstate(&[(a.clone(), 10.0)]).is_err());
+        assert!(db.get_chain_issues().is_empty());
+
+        // coins that didn't come from any block
+        db.update_balance(&a, 50.0).unwrap();
+        assert!(db.verify_initial_chainstate(&[(a.clone(), 10.0), (b.clone(), 10.0)]).is_err());
+        assert_eq!(db.get_chain_issues().len(), 1);
+    }
+
+    #[test]
+    fn only_this_networks_genesis_is_valid() {
+        let genesis: Block = Block::new_genesis();
+        assert_eq!(genesis.get_timestamp(), rblock::GENESIS_TIMESTAMP);
+        assert_eq!(Block::new_genesis().get_hash(), genesis.get_hash());
+        assert!(BlocksDB::verify_genesis(&genesis).is_ok());
+
+        // a well formed genesis made at another time starts another chain
+        let mut other: Block = with_timestamp(&genesis, genesis.get_timestamp() + 1);
+        other.rehash(&genesis.get_prev_hash());
+        assert!(other.confirm_hash());
+        let error: Status = BlocksDB::verify_genesis(&other).err().unwrap();
+        assert!(error.err.contains("not this network's genesis"));
+    }
+
+    #[test]
+    fn relative_age_goes_from_genesis_to_the_tip() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        // a chain of only genesis has no age to compare to
+        assert_eq!(db.relative_age(0).unwrap(), None);
+
+        let genesis: Block = db.get_block(0).unwrap();
+        let mut prev_block: Block = genesis.clone();
+        for offset in [10, 25, 60] {
+            let block: Block = with_timestamp(&Block::new(&prev_block, &genesis, &vec![]).unwrap(), genesis.get_timestamp() + offset);
+            db.put_block(&block).unwrap();
+            db.update_latest_block(&block).unwrap();
+            prev_block = block;
+        }
+
+        assert_eq!(db.relative_age(0).unwrap(), Some(1.0));
+        assert_eq!(db.relative_age(1).unwrap(), Some(50.0 / 60.0));
+        assert_eq!(db.relative_age(2).unwrap(), Some(35.0 / 60.0));
+        assert_eq!(db.relative_age(3).unwrap(), Some(0.0));
+        assert_eq!(db.relative_age(4).unwrap(), None);
+    }
+
+    #[test]
+    fn spendable_balance_leaves_out_immature_rewards() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        let (miner, private_miner) = key(9);
+        db.init_db(&a, &b);
+
+        for _ in 0..COINBASE_MATURITY {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        // only the reward of the first block has COINBASE_MATURITY confirmations
+        let reward: f32 = Transaction::reward_transaction(&miner).get_amount();
+        assert_eq!(db.get_balance(&miner).unwrap(), COINBASE_MATURITY as f32 * reward);
+        assert_eq!(db.spendable_balance_of(&miner).unwrap(), reward);
+        assert_eq!(db.spendable_balance_of(&a).unwrap(), db.get_balance(&a).unwrap());
+
+        // it's only what a wallet shows, a block can still spend the immature rewards
+        let transaction: Transaction = Transaction::new(&miner, &a, 2.0 * reward, &private_miner);
+        let block: Block = next_block(&mut db, &vec![transaction]);
+        db.add_block(&block).unwrap();
+
+        // two rewards are mature now and both were spent
+        assert_eq!(db.spendable_balance_of(&miner).unwrap(), 0.0);
+
+        // an address that isn't found has nothing
+        assert_eq!(db.spendable_balance_of(&key(5).0).unwrap(), 0.0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,151 @@
+use rblock::NETWORK_MAGIC;
+use rusty_leveldb::Status;
+use serde::{Serialize, Deserialize};
+
+/// The tip info two nodes exchange when they connect, to know which of them is ahead.
+///
+/// # Fields
+/// * `height` - A u64 of the height of the node's latest block
+/// * `tip_hash` - A String of the hash of the node's latest block
+/// * `total_work` - A u128 of the expected attempts it took to mine the node's whole chain
+/// * `network_magic` - A u32 of the network the node is on, NETWORK_MAGIC for this one
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct HandshakeInfo {
+    height: u64,
+    tip_hash: String,
+    total_work: u128,
+    network_magic: u32
+}
+
+#[allow(dead_code)]
+impl HandshakeInfo {
+    /// Returns the handshake of a node on this network
+    pub fn new(height: u64, tip_hash: String, total_work: u128) -> Self {
+        HandshakeInfo { height, tip_hash, total_work, network_magic: NETWORK_MAGIC }
+    }
+
+    /// returns the height of the node's latest block
+    pub fn get_height(&self) -> u64 {
+        self.height
+    }
+
+    /// returns the hash of the node's latest block
+    pub fn get_tip_hash(&self) -> String {
+        self.tip_hash.clone()
+    }
+
+    /// returns the expected attempts it took to mine the node's whole chain
+    pub fn get_total_work(&self) -> u128 {
+        self.total_work
+    }
+
+    /// returns the network the node is on
+    pub fn get_network_magic(&self) -> u32 {
+        self.network_magic
+    }
+
+    /// returns the handshake encoded as bytes to send to the other node, with the same encoding as blocks
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // unwrap since handshakes are always serializable
+        bincode::serialize(self).unwrap()
+    }
+
+    /// Reads a handshake from the bytes made by to_bytes.
+    ///
+    /// # Arguments
+    /// * `bytes` - A &[u8] which specifies the encoded handshake
+    ///
+    /// # Returns
+    /// An Result<HandshakeInfo, Status> which is the decoded handshake, or an error if the bytes aren't a handshake or are from another network.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Status> {
+        let handshake: HandshakeInfo = bincode::deserialize(bytes).map_err(|e|
+            Status::new(rusty_leveldb::StatusCode::Corruption, &format!("{e}"))
+        )?;
+
+        if handshake.network_magic != NETWORK_MAGIC {
+            return Err(Status::new(rusty_leveldb::StatusCode::InvalidArgument, &format!("Handshake is from network {:08x}", handshake.network_magic)));
+        }
+
+        Ok(handshake)
+    }
+
+    /// Decides if this node's chain is ahead of the other node's.
+    /// The chain with the most work is ahead no matter its height, since a long chain of easy blocks is cheaper to make.
+    ///
+    /// # Arguments
+    /// * `other` - A &HandshakeInfo which specifies the handshake of the other node
+    ///
+    /// # Returns
+    /// A bool which is true if this chain has more work than the other one, false if it has less or the same.
+    ///
+    pub fn has_more_work_than(&self, other: &HandshakeInfo) -> bool {
+        self.total_work > other.total_work
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rblock::Block;
+    use crate::db::BlocksDB;
+    use crate::db::tests::{key, mem_db, next_block, overwrite_block};
+
+    /// a db with count blocks on top of genesis, the first of them needing 256 expected attempts if hard is set
+    fn chain(count: usize, hard: bool) -> BlocksDB {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+
+        for _ in 0..count {
+            let block: Block = next_block(&mut db, &vec![]);
+            db.add_block(&block).unwrap();
+        }
+
+        if hard {
+            let mut block: Block = db.get_block(1).unwrap();
+            block.set_difficulty(0x77777777);
+            overwrite_block(&mut db, &block);
+        }
+
+        db
+    }
+
+    #[test]
+    fn handshake_round_trips_through_bytes() {
+        let mut db: BlocksDB = chain(2, false);
+        let handshake: HandshakeInfo = db.handshake_info().unwrap();
+        assert_eq!(handshake.get_height(), 2);
+        assert_eq!(handshake.get_tip_hash(), db.get_latest_block().unwrap().get_hash());
+        assert_eq!(handshake.get_total_work(), 3);
+        assert_eq!(handshake.get_network_magic(), NETWORK_MAGIC);
+
+        assert_eq!(HandshakeInfo::from_bytes(&handshake.to_bytes()).unwrap(), handshake);
+
+        let bytes: Vec<u8> = handshake.to_bytes();
+        assert!(HandshakeInfo::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut other_network: HandshakeInfo = handshake.clone();
+        other_network.network_magic = NETWORK_MAGIC ^ 1;
+        let error: Status = HandshakeInfo::from_bytes(&other_network.to_bytes()).err().unwrap();
+        assert_eq!(error.code, rusty_leveldb::StatusCode::InvalidArgument);
+    }
+
+    #[test]
+    fn chain_with_more_work_is_ahead_even_if_shorter() {
+        let long: HandshakeInfo = chain(4, false).handshake_info().unwrap();
+        let short: HandshakeInfo = chain(2, true).handshake_info().unwrap();
+        assert_eq!(long.get_total_work(), 5);
+        assert_eq!(short.get_total_work(), 258);
+
+        assert!(short.get_height() < long.get_height());
+        assert!(short.has_more_work_than(&long));
+        assert!(!long.has_more_work_than(&short));
+
+        // the same work isn't more
+        assert!(!long.has_more_work_than(&long.clone()));
+    }
+}
\ No newline at end of file
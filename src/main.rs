@@ -1,4 +1,7 @@
 mod db;
+mod handshake;
+mod mempool;
+mod validator;
 
 use ecdsa::secp256k1::{get_curve_precomputed_points, Secp256k1, W};
 use num_bigint::BigInt;
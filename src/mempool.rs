@@ -0,0 +1,392 @@
+use std::{collections::HashSet, fs::File, io::{BufReader, BufWriter}, path::Path};
+use ecdsa::secp256k1::Point;
+use rblock::{Block, Transaction, TRANSACTION_LIMIT_PER_BLOCK};
+use rusty_leveldb::Status;
+
+use crate::db::BlocksDB;
+
+/// A struct that represents the pool of pending transactions waiting to be put in a block.
+/// 
+/// # Fields
+/// * `transactions` - A Vec<Transaction> of the pending transactions, in the order they were received
+/// 
+#[allow(dead_code)]
+pub struct Mempool {
+    transactions: Vec<Transaction>
+}
+
+#[allow(dead_code)]
+impl Mempool {
+    /// Returns an empty mempool
+    pub fn new() -> Self {
+        Mempool { transactions: vec![] }
+    }
+
+    /// Adds a transaction to the mempool if it is valid and isn't already pending.
+    /// 
+    /// # Arguments
+    /// * `transaction` - A Transaction which specifies the transaction to add
+    /// 
+    /// # Modifications
+    /// This method adds the transaction to the pending transactions.
+    /// 
+    /// # Returns
+    /// A bool which is true if the transaction was added, false otherwise.
+    /// 
+    pub fn add_transaction(&mut self, transaction: Transaction) -> bool {
+        // Point::identity is miner reward, those are only made by miners in their own block
+        if transaction.get_sender() == Point::identity() || transaction.validate().is_err() {
+            return false;
+        }
+
+        let transaction_hash: String = transaction.get_hash();
+        if self.transactions.iter().any(|pending| pending.get_hash() == transaction_hash) {
+            return false;
+        }
+
+        self.transactions.push(transaction);
+        true
+    }
+
+    /// returns the pending transactions
+    pub fn get_transactions(&self) -> Vec<Transaction> {
+        self.transactions.clone()
+    }
+
+    /// Finds the pending transactions that double spend, the account equivalent of two transactions spending the same output.
+    /// Two transactions conflict if they have the same sender and the chainstate can pay for each of them but not for both,
+    /// so only one of them can make it into a block.
+    /// 
+    /// # Arguments
+    /// * `db` - A &mut BlocksDB which specifies the db to verify the transactions against
+    /// 
+    /// # Returns
+    /// A Vec<(String, String)> of the hashes of the conflicting transactions, the one received first on the left.
+    /// 
+    pub fn conflicts(&self, db: &mut BlocksDB) -> Vec<(String, String)> {
+        let mut conflicts: Vec<(String, String)> = Vec::new();
+
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            for other in &self.transactions[i + 1..] {
+                if transaction.get_sender() != other.get_sender() {
+                    continue;
+                }
+
+                let payable_alone: bool = db.verify_transactions(&vec![transaction.clone()]).is_ok()
+                    && db.verify_transactions(&vec![other.clone()]).is_ok();
+
+                if payable_alone && db.verify_transactions(&vec![transaction.clone(), other.clone()]).is_err() {
+                    conflicts.push((transaction.get_hash(), other.get_hash()));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    /// Takes the pending transactions to put in a block, without going over a byte budget or the transaction limit.
+    /// One transaction of the limit is left for the miner's reward.
+    /// Transactions are taken by highest fee per serialized byte first, ties are broken by taking them in order of their hash
+    /// so that two nodes with the same mempool build the same block. Selection stops at the first transaction that doesn't fit.
+    ///
+    /// # Arguments
+    /// * `max_bytes` - A usize which specifies the most serialized bytes the selected transactions can take
+    ///
+    /// # Modifications
+    /// This method removes the selected transactions from the pending transactions.
+    ///
+    /// # Returns
+    /// A Vec<Transaction> of the selected transactions, in the order they were selected.
+    ///
+    pub fn take_best_bounded(&mut self, max_bytes: usize) -> Vec<Transaction> {
+        // unwrap since transactions always serialize
+        let mut candidates: Vec<(f32, String, usize, Transaction)> = self.transactions.iter()
+            .map(|transaction| {
+                let transaction_bytes: usize = bincode::serialized_size(transaction).unwrap() as usize;
+                let fee_rate: f32 = transaction.get_fee() / transaction_bytes as f32;
+
+                (fee_rate, transaction.get_hash(), transaction_bytes, transaction.clone())
+            })
+            .collect();
+
+        // fees are validated to be finite so fee rates always compare
+        candidates.sort_by(|(fee_rate, hash, _, _), (other_fee_rate, other_hash, _, _)| {
+            other_fee_rate.total_cmp(fee_rate).then_with(|| hash.cmp(other_hash))
+        });
+
+        let mut used_bytes: usize = 0;
+        let mut selected_hashes: HashSet<String> = HashSet::new();
+        let mut selected: Vec<Transaction> = Vec::new();
+
+        for (_, transaction_hash, transaction_bytes, transaction) in candidates {
+            if selected.len() == TRANSACTION_LIMIT_PER_BLOCK - 1 || used_bytes + transaction_bytes > max_bytes {
+                break;
+            }
+
+            used_bytes += transaction_bytes;
+            selected_hashes.insert(transaction_hash);
+            selected.push(transaction);
+        }
+
+        self.transactions.retain(|transaction| !selected_hashes.contains(&transaction.get_hash()));
+        selected
+    }
+
+    /// Saves the pending transactions to a file so that they survive the node restarting.
+    /// 
+    /// # Arguments
+    /// * `path` - A &Path which specifies the file to save the transactions to
+    /// 
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the transactions were saved, or an error if they were not.
+    /// 
+    pub fn save(&self, path: &Path) -> Result<(), Status> {
+        let writer: BufWriter<File> = BufWriter::new(File::create(path)?);
+
+        bincode::serialize_into(writer, &self.transactions).map_err(|e| 
+            Status::new(rusty_leveldb::StatusCode::IOError, &format!("{e}"))
+        )?;
+
+        Ok(())
+    }
+
+    /// Loads the pending transactions saved to a file and validates them again against the db.
+    /// Transactions that were mined since they were saved, or that the chainstate can no longer pay for, are dropped.
+    /// 
+    /// # Arguments
+    /// * `path` - A &Path which specifies the file to load the transactions from
+    /// * `db` - A &mut BlocksDB which specifies the db to validate the transactions against
+    /// 
+    /// # Returns
+    /// An Result<Mempool, Status> which is the mempool with the still valid transactions, or an error if the file could not be read.
+    /// 
+    pub fn load(path: &Path, db: &mut BlocksDB) -> Result<Self, Status> {
+        let reader: BufReader<File> = BufReader::new(File::open(path)?);
+
+        let saved_transactions: Vec<Transaction> = bincode::deserialize_from(reader).map_err(|e| 
+            Status::new(rusty_leveldb::StatusCode::Corruption, &format!("{e}"))
+        )?;
+
+        let mut mempool: Mempool = Mempool::new();
+
+        for transaction in saved_transactions {
+            // already in a block
+            if db.find_transaction(&transaction.get_hash())?.is_some() {
+                continue;
+            }
+
+            mempool.add_transaction(transaction);
+        }
+
+        mempool.retain_payable(db);
+        Ok(mempool)
+    }
+
+    /// Removes the transactions that are no longer pending once a block was added to the db.
+    /// Those are the transactions in the block, and the ones the chainstate can no longer pay for because of it.
+    /// 
+    /// # Arguments
+    /// * `block` - A &Block which specifies the block that was added
+    /// * `db` - A &mut BlocksDB which specifies the db the block was added to
+    /// 
+    /// # Modifications
+    /// This method removes transactions from the pending transactions.
+    /// 
+    pub fn remove_confirmed(&mut self, block: &Block, db: &mut BlocksDB) {
+        let confirmed_hashes: HashSet<String> = block.get_transactions().iter()
+            .map(|transaction| transaction.get_hash())
+            .collect();
+
+        self.transactions.retain(|transaction| !confirmed_hashes.contains(&transaction.get_hash()));
+        self.retain_payable(db);
+    }
+
+    /// Keeps only the pending transactions that the chainstate can pay for, in the order they were received.
+    /// 
+    /// # Arguments
+    /// * `db` - A &mut BlocksDB which specifies the db to verify the transactions against
+    /// 
+    /// # Modifications
+    /// This method removes transactions from the pending transactions.
+    /// 
+    fn retain_payable(&mut self, db: &mut BlocksDB) {
+        let pending_transactions: Vec<Transaction> = std::mem::take(&mut self.transactions);
+
+        for transaction in pending_transactions {
+            // the transaction has to be payable along with the ones kept before it
+            let mut candidates: Vec<Transaction> = self.get_transactions();
+            candidates.push(transaction.clone());
+
+            if db.verify_transactions(&candidates).is_ok() {
+                self.transactions.push(transaction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{key, mem_db, next_block};
+
+    #[test]
+    fn reloaded_mempool_drops_mined_and_unpayable_transactions() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+        db.init_db(&a, &b);
+
+        let mined: Transaction = Transaction::new(&a, &b, 3., &private_a);
+        let still_valid: Transaction = Transaction::new(&b, &a, 2., &private_b);
+        let unpayable: Transaction = Transaction::new(&b, &a, 200., &private_b);
+
+        let mut mempool: Mempool = Mempool::new();
+        assert!(mempool.add_transaction(mined.clone()));
+        assert!(!mempool.add_transaction(mined.clone()));
+        assert!(mempool.add_transaction(still_valid.clone()));
+        assert!(mempool.add_transaction(unpayable.clone()));
+
+        let path = std::env::temp_dir().join("snailnote_reloaded_mempool.bin");
+        mempool.save(&path).unwrap();
+
+        let block: Block = next_block(&mut db, &vec![mined]);
+        db.add_block(&block).unwrap();
+
+        let reloaded: Mempool = Mempool::load(&path, &mut db).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let hashes: Vec<String> = reloaded.get_transactions().iter().map(|transaction| transaction.get_hash()).collect();
+        assert_eq!(hashes, vec![still_valid.get_hash()]);
+    }
+
+    #[test]
+    fn added_block_evicts_included_and_conflicting_transactions() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+        let (c, _) = key(3);
+        db.init_db(&a, &b);
+
+        let included: Transaction = Transaction::new(&a, &b, 3., &private_a);
+        let conflicting: Transaction = Transaction::new(&a, &b, 6., &private_a);
+        let unrelated: Transaction = Transaction::new(&b, &a, 1., &private_b);
+
+        let mut mempool: Mempool = Mempool::new();
+        assert!(mempool.add_transaction(included.clone()));
+        assert!(mempool.add_transaction(conflicting.clone()));
+        assert!(mempool.add_transaction(unrelated.clone()));
+
+        // after the block a only has 2 left, not enough for the conflicting transaction
+        let spent_elsewhere: Transaction = Transaction::new(&a, &c, 5., &private_a);
+        let block: Block = next_block(&mut db, &vec![included, spent_elsewhere]);
+        db.add_block(&block).unwrap();
+
+        mempool.remove_confirmed(&block, &mut db);
+
+        let hashes: Vec<String> = mempool.get_transactions().iter().map(|transaction| transaction.get_hash()).collect();
+        assert_eq!(hashes, vec![unrelated.get_hash()]);
+    }
+
+    #[test]
+    fn taken_transactions_stay_under_the_byte_budget() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        // taken in order of their hash
+        let mut transactions: Vec<Transaction> = (1..=3).map(|amount| Transaction::new(&a, &b, amount as f32, &private_a)).collect();
+        transactions.sort_by_key(|transaction| transaction.get_hash());
+        let sizes: Vec<usize> = transactions.iter().map(|transaction| bincode::serialized_size(transaction).unwrap() as usize).collect();
+
+        let mut mempool: Mempool = Mempool::new();
+        for transaction in &transactions {
+            assert!(mempool.add_transaction(transaction.clone()));
+        }
+
+        assert!(mempool.take_best_bounded(sizes[0] - 1).is_empty());
+
+        // room for the first two and part of the third
+        let budget: usize = sizes[0] + sizes[1] + sizes[2] / 2;
+        let taken: Vec<Transaction> = mempool.take_best_bounded(budget);
+
+        let taken_bytes: usize = taken.iter().map(|transaction| bincode::serialized_size(transaction).unwrap() as usize).sum();
+        assert!(taken_bytes <= budget);
+
+        let taken_hashes: Vec<String> = taken.iter().map(|transaction| transaction.get_hash()).collect();
+        assert_eq!(taken_hashes, vec![transactions[0].get_hash(), transactions[1].get_hash()]);
+
+        let left_hashes: Vec<String> = mempool.get_transactions().iter().map(|transaction| transaction.get_hash()).collect();
+        assert_eq!(left_hashes, vec![transactions[2].get_hash()]);
+    }
+
+    #[test]
+    fn equal_fee_rate_transactions_are_taken_in_hash_order() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        let transactions: Vec<Transaction> = (1..=5).map(|amount| Transaction::new(&a, &b, amount as f32, &private_a)).collect();
+        let mut expected: Vec<String> = transactions.iter().map(|transaction| transaction.get_hash()).collect();
+        expected.sort();
+
+        // two nodes that received the same transactions in a different order
+        let mut mempool: Mempool = Mempool::new();
+        let mut other_mempool: Mempool = Mempool::new();
+        for transaction in &transactions {
+            assert!(mempool.add_transaction(transaction.clone()));
+        }
+        for transaction in transactions.iter().rev() {
+            assert!(other_mempool.add_transaction(transaction.clone()));
+        }
+
+        let taken: Vec<String> = mempool.take_best_bounded(usize::MAX).iter().map(|transaction| transaction.get_hash()).collect();
+        let other_taken: Vec<String> = other_mempool.take_best_bounded(usize::MAX).iter().map(|transaction| transaction.get_hash()).collect();
+
+        assert_eq!(taken, expected);
+        assert_eq!(other_taken, expected);
+    }
+
+    #[test]
+    fn higher_fee_rate_transactions_are_taken_first() {
+        let (a, private_a) = key(1);
+        let (b, _) = key(2);
+
+        let no_fee: Transaction = Transaction::new(&a, &b, 1.0, &private_a);
+        let low_fee: Transaction = Transaction::new_with_fee(&a, &b, 1.0, 0.1, &private_a);
+        let high_fee: Transaction = Transaction::new_with_fee(&a, &b, 1.0, 0.5, &private_a);
+
+        let mut mempool: Mempool = Mempool::new();
+        for transaction in [&no_fee, &low_fee, &high_fee] {
+            assert!(mempool.add_transaction(transaction.clone()));
+        }
+
+        // room for two of them, the one without a fee is left behind
+        let budget: usize = bincode::serialized_size(&high_fee).unwrap() as usize + bincode::serialized_size(&low_fee).unwrap() as usize;
+        let taken: Vec<String> = mempool.take_best_bounded(budget).iter().map(|transaction| transaction.get_hash()).collect();
+        assert_eq!(taken, vec![high_fee.get_hash(), low_fee.get_hash()]);
+
+        let left: Vec<String> = mempool.get_transactions().iter().map(|transaction| transaction.get_hash()).collect();
+        assert_eq!(left, vec![no_fee.get_hash()]);
+    }
+
+    #[test]
+    fn conflicts_are_transactions_only_payable_one_at_a_time() {
+        let mut db: BlocksDB = mem_db();
+        let (a, private_a) = key(1);
+        let (b, private_b) = key(2);
+        let (c, _) = key(3);
+        db.init_db(&a, &b);
+
+        // a has 10, enough for either of the first two but not for both
+        let first: Transaction = Transaction::new(&a, &b, 6., &private_a);
+        let second: Transaction = Transaction::new(&a, &c, 5., &private_a);
+        let small: Transaction = Transaction::new(&a, &c, 1., &private_a);
+        let unpayable: Transaction = Transaction::new(&b, &a, 200., &private_b);
+        let other_sender: Transaction = Transaction::new(&b, &c, 9., &private_b);
+
+        let mut mempool: Mempool = Mempool::new();
+        for transaction in [&first, &second, &small, &unpayable, &other_sender] {
+            assert!(mempool.add_transaction(transaction.clone()));
+        }
+
+        assert_eq!(mempool.conflicts(&mut db), vec![(first.get_hash(), second.get_hash())]);
+    }
+}
\ No newline at end of file
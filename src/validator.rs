@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use rblock::{get_retarget_window_start, Block, BlockHeader};
+use rusty_leveldb::Status;
+
+use crate::db::{median_timestamp, BlocksDB, MEDIAN_TIME_SPAN};
+
+/// A struct that validates a chain one block at a time as it streams in, without storing it.
+/// Blocks are checked the same way BlocksDB checks them, except for the chainstate which only the db has.
+///
+/// # Fields
+/// * `tip` - An Option<Block> of the last valid block, None until genesis is accepted
+/// * `recent_headers` - A VecDeque<BlockHeader> of the headers needed for the next retarget and median time past, ordered by height
+///
+#[allow(dead_code)]
+pub struct ChainValidator {
+    tip: Option<Block>,
+    recent_headers: VecDeque<BlockHeader>
+}
+
+#[allow(dead_code)]
+impl ChainValidator {
+    /// Returns a validator that expects genesis as its first block
+    pub fn new() -> Self {
+        ChainValidator { tip: None, recent_headers: VecDeque::new() }
+    }
+
+    /// Validates the next block of the chain and makes it the tip if it's valid.
+    /// An invalid block is rejected without changing the validator, so the next block can still be tried on the same tip.
+    ///
+    /// # Arguments
+    /// * `block` - A Block which specifies the block on top of the tip, or genesis if nothing was accepted yet
+    ///
+    /// # Modifications
+    /// This method changes the tip and the recent headers if the block is valid.
+    ///
+    /// # Returns
+    /// An Result<(), Status> which is Ok(()) if the block was accepted, or an error if it is invalid.
+    ///
+    pub fn accept(&mut self, block: Block) -> Result<(), Status> {
+        match (&self.tip, self.next_difficulty()) {
+            (Some(tip), Some(next_difficulty)) => {
+                BlocksDB::verify_link(tip, &block)?;
+                BlocksDB::verify_block_with_difficulty(&block, next_difficulty)?;
+            },
+            _ => BlocksDB::verify_genesis(&block)?
+        }
+
+        self.recent_headers.push_back(block.get_header());
+
+        // only keep the headers that the next retarget window and median time past start from
+        let next_height: u64 = block.get_height().saturating_add(1);
+        let oldest_needed_height: u64 = get_retarget_window_start(next_height)
+            .min(next_height.saturating_sub(MEDIAN_TIME_SPAN));
+
+        while self.recent_headers.front().is_some_and(|header| header.get_height() < oldest_needed_height) {
+            self.recent_headers.pop_front();
+        }
+
+        self.tip = Some(block);
+        Ok(())
+    }
+
+    /// returns the last valid block, None if genesis wasn't accepted yet
+    pub fn get_tip(&self) -> Option<Block> {
+        self.tip.clone()
+    }
+
+    /// Obtains the difficulty the next block on top of the tip has to have, retargeted the same way as BlocksDB::calculate_next_difficulty.
+    ///
+    /// # Returns
+    /// An Option<u32> which is the difficulty of the next block, or None if genesis wasn't accepted yet or the tip is at the last possible height.
+    ///
+    pub fn next_difficulty(&self) -> Option<u32> {
+        let tip_header: &BlockHeader = self.recent_headers.back()?;
+        let oldest_height: u64 = self.recent_headers.front()?.get_height();
+        let window_start_height: u64 = get_retarget_window_start(tip_header.get_height().checked_add(1)?);
+
+        // the window start is always kept, accept never drops it
+        let window_start_header: &BlockHeader = &self.recent_headers[(window_start_height - oldest_height) as usize];
+
+        BlockHeader::get_supposed_difficulty(tip_header, window_start_header)
+    }
+
+    /// Obtains the median time past of the tip, the median timestamp of the last MEDIAN_TIME_SPAN blocks up to it.
+    ///
+    /// # Returns
+    /// An Option<u64> which is the median timestamp, or None if genesis wasn't accepted yet.
+    ///
+    pub fn median_time_past(&self) -> Option<u64> {
+        if self.recent_headers.is_empty() {
+            return None;
+        }
+
+        let timestamps: Vec<u64> = self.recent_headers.iter()
+            .rev()
+            .take(MEDIAN_TIME_SPAN as usize)
+            .map(|header| header.get_timestamp())
+            .collect();
+
+        Some(median_timestamp(timestamps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{key, mem_db, next_block, put_fast_blocks};
+
+    /// streams the blocks of the db up to a height into a new validator
+    fn validator_up_to(db: &mut BlocksDB, height: u64) -> ChainValidator {
+        let mut validator: ChainValidator = ChainValidator::new();
+        for block in db.get_block_range(0, height + 1).unwrap() {
+            validator.accept(block).unwrap();
+        }
+
+        validator
+    }
+
+    #[test]
+    fn streamed_chain_ends_on_the_db_tip() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 71);
+
+        // genesis has to come first
+        let mut validator: ChainValidator = ChainValidator::new();
+        assert!(validator.accept(db.get_block(1).unwrap()).is_err());
+        assert!(validator.get_tip().is_none() && validator.next_difficulty().is_none());
+
+        let mut validator: ChainValidator = validator_up_to(&mut db, 71);
+        assert_eq!(validator.get_tip().unwrap().get_hash(), db.get_latest_block().unwrap().get_hash());
+        assert_eq!(validator.next_difficulty().unwrap(), db.calculate_next_difficulty().unwrap());
+
+        // the block at the retarget height is checked against the window kept by the validator
+        let median_time_past: Option<u64> = validator.median_time_past();
+        let block: Block = next_block(&mut db, &vec![]);
+        validator.accept(block.clone()).unwrap();
+        db.add_block(&block).unwrap();
+
+        // the median time past of the tip is the effective time of the block on top of it
+        assert_eq!(median_time_past, db.effective_time(72).unwrap());
+        assert_eq!(validator.next_difficulty().unwrap(), db.calculate_next_difficulty().unwrap());
+    }
+
+    #[test]
+    fn mid_stream_bad_block_leaves_the_validator_unchanged() {
+        let mut db: BlocksDB = mem_db();
+        let (a, _) = key(1);
+        let (b, _) = key(2);
+        db.init_db(&a, &b);
+        put_fast_blocks(&mut db, 71);
+
+        let mut validator: ChainValidator = validator_up_to(&mut db, 71);
+        let tip_hash: String = validator.get_tip().unwrap().get_hash();
+        let next_difficulty: Option<u32> = validator.next_difficulty();
+        let median_time_past: Option<u64> = validator.median_time_past();
+
+        let block: Block = next_block(&mut db, &vec![]);
+
+        // keeps the easier difficulty of the blocks before the retarget
+        let mut easier: Block = block.clone();
+        easier.set_difficulty(validator.get_tip().unwrap().get_difficulty());
+        assert!(easier.confirm_hash() && easier.confirm_difficulty());
+        assert!(validator.accept(easier).is_err());
+
+        // right height for the tip, but linked to the block below it
+        let mut bad_link: Block = block.clone();
+        bad_link.rehash(&db.get_block(70).unwrap().get_hash());
+        while !bad_link.confirm_difficulty() {
+            bad_link.increment_and_hash();
+        }
+        assert!(validator.accept(bad_link).is_err());
+
+        assert_eq!(validator.get_tip().unwrap().get_hash(), tip_hash);
+        assert_eq!(validator.next_difficulty(), next_difficulty);
+        assert_eq!(validator.median_time_past(), median_time_past);
+
+        validator.accept(block.clone()).unwrap();
+        assert_eq!(validator.get_tip().unwrap().get_hash(), block.get_hash());
+    }
+}
\ No newline at end of file